@@ -30,52 +30,63 @@
 46 PRINT " 7 | 8 | 9"
 47 INPUT J
 
-50 REM validate move
-51 IF J = 1 THEN IF A <> " " THEN GOTO 31
-52 IF J = 2 THEN IF B <> " " THEN GOTO 31
-53 IF J = 3 THEN IF C <> " " THEN GOTO 31
-54 IF J = 4 THEN IF D <> " " THEN GOTO 31
-55 IF J = 5 THEN IF E <> " " THEN GOTO 31
-56 IF J = 6 THEN IF F <> " " THEN GOTO 31
-57 IF J = 7 THEN IF G <> " " THEN GOTO 31
-58 IF J = 8 THEN IF H <> " " THEN GOTO 31
-59 IF J = 9 THEN IF I <> " " THEN GOTO 31
+50 REM validate and make move
+51 SELECT CASE J
+52 CASE 1
+53 IF A <> " " THEN GOTO 31
+54 LET A = K
+55 CASE 2
+56 IF B <> " " THEN GOTO 31
+57 LET B = K
+58 CASE 3
+59 IF C <> " " THEN GOTO 31
+60 LET C = K
+61 CASE 4
+62 IF D <> " " THEN GOTO 31
+63 LET D = K
+64 CASE 5
+65 IF E <> " " THEN GOTO 31
+66 LET E = K
+67 CASE 6
+68 IF F <> " " THEN GOTO 31
+69 LET F = K
+70 CASE 7
+71 IF G <> " " THEN GOTO 31
+72 LET G = K
+73 CASE 8
+74 IF H <> " " THEN GOTO 31
+75 LET H = K
+76 CASE 9
+77 IF I <> " " THEN GOTO 31
+78 LET I = K
+79 CASE ELSE
+80 GOTO 31
+81 END SELECT
 
-60 REM make move
-61 IF J = 1 THEN LET A = K
-62 IF J = 2 THEN LET B = K
-63 IF J = 3 THEN LET C = K
-64 IF J = 4 THEN LET D = K
-65 IF J = 5 THEN LET E = K
-66 IF J = 6 THEN LET F = K
-67 IF J = 7 THEN LET G = K
-68 IF J = 8 THEN LET H = K
-69 IF J = 9 THEN LET I = K
+90 REM check for win
+91 IF A = K THEN IF B = K THEN IF C = K THEN GOTO 200
+92 IF A = K THEN IF D = K THEN IF G = K THEN GOTO 200
+93 IF A = K THEN IF E = K THEN IF I = K THEN GOTO 200
+94 IF B = K THEN IF E = K THEN IF H = K THEN GOTO 200
+95 IF C = K THEN IF F = K THEN IF I = K THEN GOTO 200
+96 IF C = K THEN IF E = K THEN IF G = K THEN GOTO 200
+97 IF D = K THEN IF E = K THEN IF F = K THEN GOTO 200
+98 IF G = K THEN IF H = K THEN IF I = K THEN GOTO 200
 
-70 REM check for win
-71 IF A = K THEN IF B = K THEN IF C = K THEN GOTO 100
-72 IF A = K THEN IF D = K THEN IF G = K THEN GOTO 100
-73 IF A = K THEN IF E = K THEN IF I = K THEN GOTO 100
-74 IF B = K THEN IF E = K THEN IF H = K THEN GOTO 100
-75 IF C = K THEN IF F = K THEN IF I = K THEN GOTO 100
-76 IF C = K THEN IF E = K THEN IF G = K THEN GOTO 100
-77 IF D = K THEN IF E = K THEN IF F = K THEN GOTO 100
-78 IF G = K THEN IF H = K THEN IF I = K THEN GOTO 100
+100 REM check for draw
+101 IF A <> " " THEN IF B <> " " THEN IF C <> " " THEN IF D <> " " THEN IF E <> " " THEN IF F <> " " THEN IF G <> " " THEN IF H <> " " THEN IF I <> " " THEN GOTO 300
 
-80 REM check for draw
-81 IF A <> " " THEN IF B <> " " THEN IF C <> " " THEN IF D <> " " THEN IF E <> " " THEN IF F <> " " THEN IF G <> " " THEN IF H <> " " THEN IF I <> " " THEN GOTO 200
+110 REM switch player
+111 IF K = "X" THEN GOTO 114
+112 LET K = "X"
+113 GOTO 31
+114 LET K = "O"
+115 GOTO 31
 
-90 REM switch player
-91 IF K = "X" THEN GOTO 94
-92 LET K = "X"
-93 GOTO 31
-94 LET K = "O"
-95 GOTO 31
+200 PRINT "PLAYER", K, "WINS"
+201 GOTO 400
 
-100 PRINT "PLAYER", K, "WINS"
-101 GOTO 300
+300 PRINT "DRAW"
+301 GOTO 400
 
-200 PRINT "DRAW"
-201 GOTO 300
-
-300 END
+400 END
@@ -1,3 +1,5 @@
+use crate::lexer::Dialect;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RelationOperator {
     Equal,
@@ -12,6 +14,7 @@ pub enum RelationOperator {
 pub enum UnaryOperator {
     Plus,
     Minus,
+    Not,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -20,6 +23,10 @@ pub enum ArithmeticOperator {
     Subtract,
     Multiply,
     Divide,
+    // Bitwise on integers, logical on the -1 and 0 that relations return
+    And,
+    Or,
+    Xor,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,9 +42,45 @@ pub struct BinaryExpression {
     pub right: Box<Expression>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelationExpression {
+    pub operator: RelationOperator,
+    pub left: Box<Expression>,
+    pub right: Box<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallExpression {
+    pub callee: Identifier,
+    pub arguments: Vec<Expression>,
+}
+
+// DIM without subscripts declares a plain variable, for OPTION EXPLICIT
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayDeclaration {
+    pub name: String,
+    pub dimensions: Vec<Expression>,
+}
+
+// Right hand side of MAT <array> = ...
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatrixExpression {
+    Array(Identifier),
+    Add(Identifier, Identifier),
+    Subtract(Identifier, Identifier),
+    Multiply(Identifier, Identifier),
+    Transpose(Identifier),
+    Inverse(Identifier),
+    // ZER, CON and IDN redimension the target when given a size
+    Zero(Vec<Expression>),
+    Constant(Vec<Expression>),
+    Identity(Vec<Expression>),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct VarDeclaration {
     pub name: String,
+    pub indices: Vec<Expression>,
     pub value: Expression,
 }
 
@@ -56,21 +99,65 @@ pub enum Literal {
 pub enum Expression {
     UnaryExpression(UnaryExpression),
     BinaryExpression(BinaryExpression),
+    RelationExpression(RelationExpression),
+    CallExpression(CallExpression),
     Identifier(Identifier),
     Literal(Literal),
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct IfCondition {
-    pub operator: RelationOperator,
-    pub left: Expression,
-    pub right: Expression,
+pub enum CaseTest {
+    Value(Expression),
+    Range {
+        from: Expression,
+        to: Expression,
+    },
+    Is {
+        operator: RelationOperator,
+        value: Expression,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileMode {
+    Input,
+    Output,
+    Append,
+    Random,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDeclaration {
+    pub width: Expression,
+    pub variable: Identifier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collation {
+    Ordinal,
+    CaseInsensitive,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+pub enum OptionDirective {
+    Compare(Collation),
+    Base(usize),
+    Explicit,
+    Strict,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResumeTarget {
+    Retry,
+    Next,
+    Line(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Statement {
     IfStatement {
-        condition: IfCondition,
+        condition: Expression,
         then: Box<Statement>,
     },
     PrintStatement {
@@ -79,23 +166,174 @@ pub enum Statement {
     InputStatement {
         variables: Vec<Identifier>,
     },
+    OpenStatement {
+        path: Expression,
+        mode: FileMode,
+        channel: Expression,
+        length: Option<Expression>,
+    },
+    FieldStatement {
+        channel: Expression,
+        fields: Vec<FieldDeclaration>,
+    },
+    GetStatement {
+        channel: Expression,
+        record: Option<Expression>,
+    },
+    PutStatement {
+        channel: Expression,
+        record: Option<Expression>,
+    },
+    PrintFileStatement {
+        channel: Expression,
+        expressions: Vec<Expression>,
+    },
+    InputFileStatement {
+        channel: Expression,
+        variables: Vec<Identifier>,
+    },
+    LineInputFileStatement {
+        channel: Expression,
+        variable: Identifier,
+    },
+    CloseStatement {
+        channels: Vec<Expression>,
+    },
     VarStatement {
         declaration: VarDeclaration,
     },
+    DimStatement {
+        declarations: Vec<ArrayDeclaration>,
+    },
+    EraseStatement {
+        arrays: Vec<Identifier>,
+    },
+    SwapStatement {
+        left: Expression,
+        right: Expression,
+    },
+    ClearStatement,
+    VarsStatement,
+    ConstStatement {
+        name: String,
+        value: Expression,
+    },
+    DataStatement {
+        values: Vec<Expression>,
+    },
+    ReadStatement {
+        variables: Vec<Identifier>,
+    },
+    RestoreStatement {
+        location: Option<Expression>,
+    },
+    MatStatement {
+        target: Identifier,
+        value: MatrixExpression,
+    },
+    MatReadStatement {
+        arrays: Vec<Identifier>,
+    },
+    MatPrintStatement {
+        arrays: Vec<Identifier>,
+    },
+    SpawnStatement {
+        location: Expression,
+    },
+    YieldStatement,
+    WaitStatement,
+    AssertStatement {
+        condition: Expression,
+        message: Option<Expression>,
+    },
+    TestStatement,
     GoToStatement {
         location: Expression,
     },
     GoSubStatement {
         location: Expression,
     },
+    SelectStatement {
+        discriminant: Expression,
+    },
+    CaseStatement {
+        tests: Vec<CaseTest>,
+    },
+    CaseElseStatement,
+    EndSelectStatement,
+    OnErrorStatement {
+        location: Expression,
+    },
+    ResumeStatement {
+        target: ResumeTarget,
+    },
+    OnTimerStatement {
+        interval: Expression,
+        location: Expression,
+    },
+    OnKeyStatement {
+        location: Expression,
+    },
+    TimerStatement {
+        enabled: bool,
+    },
+    KeyStatement {
+        enabled: bool,
+    },
     NewStatement,
     RunStatement,
-    ReturnStatement,
-    EndStatement,
+    ReturnStatement {
+        location: Option<Expression>,
+    },
+    EndStatement {
+        code: Option<Expression>,
+    },
+    SystemStatement {
+        code: Option<Expression>,
+    },
+    StopStatement,
+    ContStatement,
+    TronStatement {
+        variables: bool,
+    },
+    TroffStatement,
+    RandomizeStatement {
+        seed: Option<Expression>,
+    },
+    SleepStatement {
+        duration: Expression,
+    },
+    PauseStatement {
+        duration: Expression,
+    },
     HelpStatement,
     ClsStatement,
     ListStatement,
-    RemStatement,
+    RemStatement {
+        text: String,
+    },
+    OptionStatement {
+        directive: OptionDirective,
+    },
+    DialectStatement {
+        dialect: Dialect,
+    },
+    PokeStatement {
+        address: Expression,
+        value: Expression,
+    },
+    ChainStatement {
+        path: Expression,
+    },
+    MergeStatement {
+        path: Expression,
+    },
+    IncludeStatement {
+        path: Expression,
+    },
+    CommonStatement {
+        variables: Vec<Identifier>,
+    },
     LoadStatement,
     SaveStatement,
     Empty,
@@ -105,5 +343,7 @@ pub enum Statement {
 pub struct Line {
     pub number: Option<usize>,
     pub statement: Statement,
+    // Text of a trailing ' comment
+    pub comment: Option<String>,
     pub source: String,
 }
@@ -0,0 +1,64 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::io;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millisecond: u32,
+}
+
+impl DateTime {
+    pub fn seconds_since_midnight(&self) -> f32 {
+        (self.hour * 3600 + self.minute * 60 + self.second) as f32
+            + self.millisecond as f32 / 1000.0
+    }
+
+    pub fn date(&self) -> String {
+        format!("{:02}-{:02}-{:04}", self.month, self.day, self.year)
+    }
+
+    pub fn time(&self) -> String {
+        format!("{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+pub trait Clock {
+    fn now(&self) -> DateTime;
+    fn sleep(&self, milliseconds: u64) -> Pin<Box<dyn Future<Output = ()> + '_>>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime {
+        io::now()
+    }
+
+    fn sleep(&self, milliseconds: u64) -> Pin<Box<dyn Future<Output = ()> + '_>> {
+        Box::pin(io::sleep(milliseconds))
+    }
+}
+
+// Always reports the same time and never waits
+#[cfg(test)]
+pub struct FixedClock {
+    pub time: DateTime,
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime {
+        self.time
+    }
+
+    fn sleep(&self, _milliseconds: u64) -> Pin<Box<dyn Future<Output = ()> + '_>> {
+        Box::pin(std::future::ready(()))
+    }
+}
@@ -9,6 +9,7 @@ pub enum SyntaxError {
     UnexpectedToken(Token),
     UnexpectedIdentifier(String, usize),
     InvalidVariableName(String, usize),
+    MissingLineNumber(usize),
 }
 
 impl fmt::Display for SyntaxError {
@@ -33,6 +34,9 @@ impl fmt::Display for SyntaxError {
             Self::UnexpectedIdentifier(name, pos) => {
                 write!(f, "Unexpected identifier '{}' at position {}", name, pos)
             }
+            Self::MissingLineNumber(pos) => {
+                write!(f, "Missing line number at position {}", pos)
+            }
         }
     }
 }
@@ -46,12 +50,76 @@ pub enum RuntimeError {
     InvalidState(String),
     IllegalLineNumber(String),
     UndefinedVariable(String, usize),
+    ResumeWithoutError,
+    CantContinue,
+    UndefinedFunction(String, usize),
+    IllegalFunctionCall(String, usize),
+    FileNotFound(String),
+    FileAlreadyOpen(usize),
+    BadFileNumber(usize),
+    BadFileMode(usize),
+    InputPastEnd(usize),
+    FieldOverflow(usize),
+    BadRecordNumber(usize),
+    SubscriptOutOfRange(usize),
+    DuplicateDefinition(String, usize),
+    OutOfData(usize),
+    DimensionMismatch(usize),
+    Overflow(usize),
+    TypeMismatch(usize),
+    VariableNotDeclared(String, usize),
+    AssignmentToConstant(String, usize),
+    CircularInclude(String),
+    LineConflict(String, usize),
+    IncludeSyntaxError(String, usize, SyntaxError),
+    AssertionFailed(String, Option<usize>),
+    FileWriteError(String, String),
+    // Raised by a line that came from an INCLUDE file
+    Included(String, Box<RuntimeError>),
 }
 
 impl RuntimeError {
     pub fn new(message: &str) -> Self {
         Self::Generic(message.to_string())
     }
+
+    // Codes are exposed to programs through ERR, never renumber them
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Generic(_) => 1,
+            Self::SyntaxError(_) => 2,
+            Self::NotImplemented(_) => 3,
+            Self::InvalidOperation(_) => 4,
+            Self::InvalidState(_) => 5,
+            Self::IllegalLineNumber(_) => 6,
+            Self::UndefinedVariable(_, _) => 7,
+            Self::ResumeWithoutError => 8,
+            Self::CantContinue => 9,
+            Self::UndefinedFunction(_, _) => 10,
+            Self::IllegalFunctionCall(_, _) => 11,
+            Self::FileNotFound(_) => 12,
+            Self::FileAlreadyOpen(_) => 13,
+            Self::BadFileNumber(_) => 14,
+            Self::BadFileMode(_) => 15,
+            Self::InputPastEnd(_) => 16,
+            Self::FieldOverflow(_) => 17,
+            Self::BadRecordNumber(_) => 18,
+            Self::SubscriptOutOfRange(_) => 19,
+            Self::DuplicateDefinition(_, _) => 20,
+            Self::OutOfData(_) => 21,
+            Self::DimensionMismatch(_) => 22,
+            Self::Overflow(_) => 23,
+            Self::TypeMismatch(_) => 24,
+            Self::VariableNotDeclared(_, _) => 25,
+            Self::AssignmentToConstant(_, _) => 26,
+            Self::CircularInclude(_) => 27,
+            Self::LineConflict(_, _) => 28,
+            Self::AssertionFailed(_, _) => 29,
+            Self::FileWriteError(_, _) => 30,
+            Self::IncludeSyntaxError(_, _, _) => 2,
+            Self::Included(_, error) => error.code(),
+        }
+    }
 }
 
 impl fmt::Display for RuntimeError {
@@ -68,6 +136,54 @@ impl fmt::Display for RuntimeError {
                 line
             ),
             Self::UndefinedVariable(name, line) => write!(f, "Undefined variable at line {}: {}", line, name),
+            Self::ResumeWithoutError => write!(f, "RESUME without error"),
+            Self::CantContinue => write!(f, "Can't continue"),
+            Self::UndefinedFunction(name, line) => {
+                write!(f, "Undefined function at line {}: {}", line, name)
+            }
+            Self::IllegalFunctionCall(name, line) => {
+                write!(f, "Illegal function call at line {}: {}", line, name)
+            }
+            Self::FileNotFound(name) => write!(f, "File not found: {}", name),
+            Self::FileAlreadyOpen(number) => write!(f, "File #{} already open", number),
+            Self::BadFileNumber(number) => write!(f, "Bad file number: #{}", number),
+            Self::BadFileMode(number) => write!(f, "Bad file mode for #{}", number),
+            Self::InputPastEnd(number) => write!(f, "Input past end of #{}", number),
+            Self::FieldOverflow(number) => write!(f, "Field overflow in #{}", number),
+            Self::BadRecordNumber(number) => write!(f, "Bad record number: {}", number),
+            Self::SubscriptOutOfRange(line) => write!(f, "Subscript out of range at line {}", line),
+            Self::DuplicateDefinition(name, line) => {
+                write!(f, "Duplicate definition at line {}: {}", line, name)
+            }
+            Self::OutOfData(line) => write!(f, "Out of DATA at line {}", line),
+            Self::DimensionMismatch(line) => write!(f, "Dimension mismatch at line {}", line),
+            Self::Overflow(line) => write!(f, "Overflow at line {}", line),
+            Self::TypeMismatch(line) => write!(f, "Type mismatch at line {}", line),
+            Self::VariableNotDeclared(name, line) => {
+                write!(f, "Variable not declared at line {}: {}", line, name)
+            }
+            Self::AssignmentToConstant(name, line) => {
+                write!(f, "Assignment to constant at line {}: {}", line, name)
+            }
+            Self::CircularInclude(chain) => write!(f, "Circular INCLUDE: {}", chain),
+            Self::LineConflict(file, line) => {
+                write!(
+                    f,
+                    "Line {} of {} conflicts with an existing line",
+                    line, file
+                )
+            }
+            Self::IncludeSyntaxError(file, line, error) => {
+                write!(f, "Syntax error in {} line {}: {}", file, line, error)
+            }
+            Self::AssertionFailed(detail, Some(line)) => {
+                write!(f, "Assertion failed at line {}: {}", line, detail)
+            }
+            Self::AssertionFailed(detail, None) => write!(f, "Assertion failed: {}", detail),
+            Self::FileWriteError(name, reason) => {
+                write!(f, "Cannot write {}: {}", name, reason)
+            }
+            Self::Included(file, error) => write!(f, "{}: {}", file, error),
         }
     }
 }
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use crate::ast::FileMode;
+use crate::errors::RuntimeError;
+use crate::io::{read_file, write_file};
+
+pub struct Field {
+    pub width: usize,
+    pub variable: String,
+}
+
+pub struct FileChannel {
+    pub name: String,
+    pub mode: FileMode,
+    contents: String,
+    position: usize,
+    record_length: usize,
+    record: usize,
+    fields: Vec<Field>,
+}
+
+impl FileChannel {
+    pub fn eof(&self) -> bool {
+        self.position >= self.contents.len()
+    }
+
+    fn skip_separator(&mut self) {
+        let rest = &self.contents[self.position..];
+        let rest = rest.strip_prefix('\r').unwrap_or(rest);
+
+        if rest.starts_with(',') || rest.starts_with('\n') {
+            self.position = self.contents.len() - rest.len() + 1;
+        }
+    }
+
+    // Fields are separated by commas or newlines and may be quoted
+    pub fn read_field(&mut self) -> Option<String> {
+        if self.eof() {
+            return None;
+        }
+
+        let rest = self.contents[self.position..].trim_start_matches(' ');
+        self.position = self.contents.len() - rest.len();
+
+        let field = if let Some(quoted) = rest.strip_prefix('"') {
+            // A doubled quote stands for one quote inside the field
+            let mut field = String::new();
+            let mut end = quoted.len();
+            let mut chars = quoted.char_indices().peekable();
+            while let Some((index, char)) = chars.next() {
+                if char != '"' {
+                    field.push(char);
+                } else if chars.next_if(|(_, next)| *next == '"').is_some() {
+                    field.push('"');
+                } else {
+                    end = index;
+                    break;
+                }
+            }
+            self.position += end + 2;
+            self.position = self.position.min(self.contents.len());
+
+            // Drop anything between the closing quote and the separator
+            let rest = &self.contents[self.position..];
+            let skip = rest.find([',', '\n']).unwrap_or(rest.len());
+            self.position += skip;
+
+            field
+        } else {
+            let end = rest.find([',', '\n']).unwrap_or(rest.len());
+            self.position += end;
+
+            rest[..end].trim_end().to_string()
+        };
+
+        self.skip_separator();
+
+        Some(field)
+    }
+
+    pub fn read_line(&mut self) -> Option<String> {
+        if self.eof() {
+            return None;
+        }
+
+        let rest = &self.contents[self.position..];
+        let end = rest.find('\n').unwrap_or(rest.len());
+        let line = rest[..end].trim_end_matches('\r').to_string();
+        self.position = (self.position + end + 1).min(self.contents.len());
+
+        Some(line)
+    }
+
+    pub fn write(&mut self, text: &str) {
+        self.contents.push_str(text);
+    }
+
+    pub fn fields(&self) -> &Vec<Field> {
+        &self.fields
+    }
+
+    pub fn set_fields(&mut self, number: usize, fields: Vec<Field>) -> Result<(), RuntimeError> {
+        if fields.iter().map(|field| field.width).sum::<usize>() > self.record_length {
+            return Err(RuntimeError::FieldOverflow(number));
+        }
+
+        self.fields = fields;
+
+        Ok(())
+    }
+
+    // Records are counted from 1 and measured in characters
+    fn seek(&mut self, record: Option<usize>) -> Result<usize, RuntimeError> {
+        let record = record.unwrap_or(self.record + 1);
+        if record == 0 {
+            return Err(RuntimeError::BadRecordNumber(record));
+        }
+
+        self.record = record;
+
+        Ok((record - 1) * self.record_length)
+    }
+
+    pub fn read_record(&mut self, record: Option<usize>) -> Result<Vec<String>, RuntimeError> {
+        let start = self.seek(record)?;
+
+        let mut chars = self.contents.chars().skip(start);
+        let mut values: Vec<String> = vec![];
+        for field in &self.fields {
+            let value: String = chars.by_ref().take(field.width).collect();
+            values.push(format!("{:width$}", value, width = field.width));
+        }
+
+        Ok(values)
+    }
+
+    pub fn write_record(
+        &mut self,
+        record: Option<usize>,
+        values: Vec<String>,
+    ) -> Result<(), RuntimeError> {
+        let start = self.seek(record)?;
+
+        let mut data: String = self
+            .fields
+            .iter()
+            .zip(values)
+            .map(|(field, value)| {
+                let value: String = value.chars().take(field.width).collect();
+                format!("{:width$}", value, width = field.width)
+            })
+            .collect();
+        data = format!("{:width$}", data, width = self.record_length);
+
+        let mut chars: Vec<char> = self.contents.chars().collect();
+        if chars.len() < start + self.record_length {
+            chars.resize(start + self.record_length, ' ');
+        }
+        chars.splice(start..start + self.record_length, data.chars());
+        self.contents = chars.into_iter().collect();
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), RuntimeError> {
+        if self.mode == FileMode::Input {
+            return Ok(());
+        }
+
+        write_file(&self.name, &self.contents)
+            .map_err(|reason| RuntimeError::FileWriteError(self.name.clone(), reason))
+    }
+}
+
+pub struct Files {
+    channels: HashMap<usize, FileChannel>,
+}
+
+impl Files {
+    pub fn new() -> Files {
+        Files {
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn open(
+        &mut self,
+        number: usize,
+        name: String,
+        mode: FileMode,
+        record_length: usize,
+    ) -> Result<(), RuntimeError> {
+        if self.channels.contains_key(&number) {
+            return Err(RuntimeError::FileAlreadyOpen(number));
+        }
+
+        let contents = match mode {
+            FileMode::Input => match read_file(&name) {
+                Some(contents) => contents,
+                None => return Err(RuntimeError::FileNotFound(name)),
+            },
+            FileMode::Output => String::new(),
+            FileMode::Append | FileMode::Random => read_file(&name).unwrap_or_default(),
+        };
+
+        self.channels.insert(
+            number,
+            FileChannel {
+                name,
+                mode,
+                contents,
+                position: 0,
+                record_length,
+                record: 0,
+                fields: vec![],
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn get(&self, number: usize) -> Result<&FileChannel, RuntimeError> {
+        self.channels
+            .get(&number)
+            .ok_or(RuntimeError::BadFileNumber(number))
+    }
+
+    pub fn get_mut(&mut self, number: usize) -> Result<&mut FileChannel, RuntimeError> {
+        self.channels
+            .get_mut(&number)
+            .ok_or(RuntimeError::BadFileNumber(number))
+    }
+
+    pub fn close(&mut self, number: usize) -> Result<(), RuntimeError> {
+        match self.channels.remove(&number) {
+            Some(channel) => channel.flush(),
+            None => Err(RuntimeError::BadFileNumber(number)),
+        }
+    }
+
+    // Every channel is closed even when an earlier one fails to write
+    pub fn close_all(&mut self) -> Result<(), RuntimeError> {
+        let mut result = Ok(());
+        for (_, channel) in self.channels.drain() {
+            if let Err(error) = channel.flush() {
+                result = result.and(Err(error));
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(contents: &str) -> FileChannel {
+        FileChannel {
+            name: String::from("test"),
+            mode: FileMode::Input,
+            contents: contents.to_string(),
+            position: 0,
+            record_length: 0,
+            record: 0,
+            fields: vec![],
+        }
+    }
+
+    #[test]
+    fn read_field_unescapes_doubled_quotes() {
+        let mut channel = channel("\"say \"\"hi\"\"\",\"a,b\"\nplain\n");
+
+        assert_eq!(channel.read_field().as_deref(), Some("say \"hi\""));
+        assert_eq!(channel.read_field().as_deref(), Some("a,b"));
+        assert_eq!(channel.read_field().as_deref(), Some("plain"));
+        assert_eq!(channel.read_field(), None);
+    }
+}
@@ -1,16 +1,28 @@
 use crate::ast::{
-    ArithmeticOperator, BinaryExpression, Expression, Identifier, IfCondition, Line, Literal,
-    RelationOperator, Statement, UnaryExpression, UnaryOperator, VarDeclaration,
+    ArithmeticOperator, ArrayDeclaration, BinaryExpression, CallExpression, CaseTest, Collation,
+    Expression, FieldDeclaration, FileMode, Identifier, Line, Literal, MatrixExpression,
+    OptionDirective, RelationExpression, RelationOperator, ResumeTarget, Statement,
+    UnaryExpression, UnaryOperator, VarDeclaration,
 };
+use crate::clock::{Clock, SystemClock};
 use crate::errors::RuntimeError;
+use crate::files::{Field, FileChannel, Files};
+use crate::lexer::Dialect;
+use crate::matrix::Matrix;
+use crate::memory::{Memory, Routine, MEMORY_SIZE};
 use crate::parser::Parser;
-use crate::program::Program;
-use std::collections::HashMap;
+use crate::program::{Program, MAX_LINES};
+use crate::random::Random;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 
 use wasm_bindgen::prelude::*;
 
-use crate::io::{clear, load_file, read_line, save_file, set_prompt, write_line};
+use crate::io::{
+    clear, command_arguments, environment, exit, load_file, poll_key, read_file, read_line,
+    save_file, set_prompt, set_raw_mode, write, write_line, yield_now,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 enum Value {
@@ -31,17 +43,220 @@ impl fmt::Display for Value {
     }
 }
 
+const DEFAULT_RECORD_LENGTH: usize = 128;
+
+// Tiny BASIC system area, read only words describing the program space
+const PROGRAM_START: usize = 0x0100;
+const SYSTEM_PROGRAM_START: u16 = 0x0020;
+const SYSTEM_MEMORY_END: u16 = 0x0022;
+const SYSTEM_PROGRAM_END: u16 = 0x0024;
+
+// Binary values are stored one byte per character, as in MKI$ and CVI
+fn encode_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| *byte as char).collect()
+}
+
+fn from_integer(integer: u16) -> f32 {
+    integer as i16 as f32
+}
+
+// A nested INCLUDE names its file relative to the directory of the including file
+fn resolve_include(parent: &str, name: &str) -> String {
+    match parent.rfind('/') {
+        Some(index) => format!("{}/{}", &parent[..index], name),
+        None => name.to_string(),
+    }
+}
+
+fn decode_bytes<const N: usize>(string: &str) -> Option<[u8; N]> {
+    let mut bytes = [0u8; N];
+    let mut chars = string.chars();
+    for byte in bytes.iter_mut() {
+        *byte = u8::try_from(chars.next()? as u32).ok()?;
+    }
+
+    Some(bytes)
+}
+
 type InterpreterResult = std::result::Result<Value, RuntimeError>;
 
+#[derive(PartialEq)]
+enum TraceMode {
+    Off,
+    Lines,
+    Variables,
+}
+
+struct TimerEvent {
+    interval: f32,
+    handler: usize,
+    enabled: bool,
+    last: f32,
+}
+
+struct KeyEvent {
+    handler: usize,
+    enabled: bool,
+}
+
+#[derive(Default)]
+struct Events {
+    timer: Option<TimerEvent>,
+    key: Option<KeyEvent>,
+    // Drained by INKEY$ while expressions are evaluated
+    keys: RefCell<VecDeque<char>>,
+    // Queued keys that already ran the ON KEY handler, one run per key
+    keys_handled: Cell<usize>,
+    // GOSUB stack depth of the running handler, handlers never re-enter
+    handler_depth: Option<usize>,
+    // Set when INKEY$ found no key, so a polling loop lets the page run
+    idle: Cell<bool>,
+}
+
+struct Array {
+    dimensions: Vec<usize>,
+    // Lowest subscript, set by OPTION BASE
+    base: usize,
+    values: Vec<Value>,
+}
+
+impl Array {
+    fn new(dimensions: Vec<usize>, initial: Value) -> Array {
+        let size = dimensions.iter().product();
+
+        Array {
+            dimensions,
+            base: 0,
+            values: vec![initial; size],
+        }
+    }
+
+    // DIM A(10) declares the subscripts base through 10
+    fn with_bounds(bounds: &[usize], base: usize, initial: Value) -> Option<Array> {
+        if bounds.iter().any(|bound| *bound < base) {
+            return None;
+        }
+
+        let dimensions = bounds.iter().map(|bound| bound + 1 - base).collect();
+
+        Some(Array {
+            base,
+            ..Array::new(dimensions, initial)
+        })
+    }
+
+    fn offset(&self, indices: &[usize]) -> Option<usize> {
+        if indices.len() != self.dimensions.len() {
+            return None;
+        }
+
+        let mut offset = 0;
+        for (index, dimension) in indices.iter().zip(&self.dimensions) {
+            let index = index.checked_sub(self.base)?;
+            if index >= *dimension {
+                return None;
+            }
+            offset = offset * dimension + index;
+        }
+
+        Some(offset)
+    }
+
+    // MAT works on subscripts from 1, with OPTION BASE 0 row and column 0 are left alone
+    fn matrix_shape(&self) -> Option<(usize, usize)> {
+        let upper = |dimension: &usize| dimension + self.base - 1;
+
+        match self.dimensions.as_slice() {
+            [rows] => Some((upper(rows), 1)),
+            [rows, columns] => Some((upper(rows), upper(columns))),
+            _ => None,
+        }
+    }
+
+    fn matrix_offset(&self, row: usize, column: usize) -> Option<usize> {
+        match self.dimensions.len() {
+            1 => self.offset(&[row + 1]),
+            _ => self.offset(&[row + 1, column + 1]),
+        }
+    }
+}
+
+// Program wide OPTION directives, collected before a run starts
+struct Options {
+    collation: Collation,
+    base: usize,
+    explicit: bool,
+    strict: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            collation: Collation::Ordinal,
+            base: 0,
+            explicit: false,
+            strict: false,
+        }
+    }
+}
+
+// Position of the next READ, as a line and an item within its DATA
+#[derive(Default)]
+struct DataPointer {
+    line: usize,
+    item: usize,
+}
+
+// A suspended task, the running one keeps its state in the context
+struct Task {
+    id: usize,
+    parent: Option<usize>,
+    current_line: usize,
+    stack: Vec<usize>,
+    handler_depth: Option<usize>,
+}
+
+// Task 0 is the main program, tasks only switch on YIELD, WAIT or when one ends
+#[derive(Default)]
+struct Tasks {
+    current: usize,
+    parent: Option<usize>,
+    suspended: VecDeque<Task>,
+    next_id: usize,
+}
+
+struct TrappedError {
+    code: u16,
+    line: usize,
+}
+
 pub struct RuntimeContext {
     variables: HashMap<String, Value>,
+    arrays: HashMap<String, Array>,
     program: Program,
     stack: Vec<usize>,
     current_line: usize,
+    error_handler: Option<usize>,
+    error: Option<TrappedError>,
+    trace: TraceMode,
+    random: Random,
+    clock: Box<dyn Clock>,
+    events: Events,
+    files: Files,
+    memory: Memory,
+    options: Options,
+    constants: HashSet<String>,
+    data: DataPointer,
+    // Line numbers loaded by INCLUDE and the file each came from
+    included: HashMap<usize, String>,
+    tasks: Tasks,
+    // Process exit status set by END <n>
+    exit_code: i32,
 }
 
 pub enum InterpreterState {
     Running,
+    Paused,
     Stopped,
 }
 
@@ -49,6 +264,7 @@ pub enum InterpreterState {
 pub struct Interpreter {
     context: RuntimeContext,
     state: InterpreterState,
+    dialect: Dialect,
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
@@ -58,44 +274,360 @@ impl Interpreter {
         Interpreter {
             context: RuntimeContext {
                 variables: HashMap::new(),
+                arrays: HashMap::new(),
                 program: Program::new(),
                 stack: vec![0],
                 current_line: 0,
+                error_handler: None,
+                error: None,
+                trace: TraceMode::Off,
+                random: Random::new(),
+                clock: Box::new(SystemClock),
+                events: Events::default(),
+                files: Files::new(),
+                memory: Memory::new(),
+                options: Options::default(),
+                constants: HashSet::new(),
+                data: DataPointer::default(),
+                included: HashMap::new(),
+                tasks: Tasks::default(),
+                exit_code: 0,
             },
             state: InterpreterState::Stopped,
+            dialect: Dialect::Standard,
         }
     }
 
-    fn reset(&mut self) {
+    fn reset(&mut self) -> Result<(), RuntimeError> {
+        self.clear_state();
+        self.context.files.close_all()
+    }
+
+    // Everything a run leaves behind except the open files
+    fn clear_state(&mut self) {
         self.context.current_line = 0;
         self.context.stack.clear();
         self.context.variables.clear();
+        self.context.constants.clear();
+        self.context.arrays.clear();
+        self.context.error_handler = None;
+        self.context.error = None;
+        self.context.events = Events::default();
+        self.context.data = DataPointer::default();
+        self.context.tasks = Tasks::default();
+        self.context.exit_code = 0;
+    }
+
+    fn new_program(&mut self) -> Result<(), RuntimeError> {
+        self.state = InterpreterState::Stopped;
+        self.context.files.close_all()?;
+        self.replace_program(vec![])
     }
 
-    fn new_program(&mut self) {
+    // Swaps in a whole new program, as NEW, LOAD, CHAIN and scripts do
+    fn replace_program(&mut self, lines: Vec<Line>) -> Result<(), RuntimeError> {
+        Self::check_line_numbers(&lines)?;
+
         self.context.program.clear();
-        self.reset();
+        self.context.included.clear();
+        self.context.options = Options::default();
+        self.clear_state();
+        self.set_lines(lines)?;
+
+        Ok(())
     }
 
     fn visit_expression(&self, expression: &Expression) -> InterpreterResult {
         match expression {
-            Expression::Identifier(identifier) => {
-                let value = self.context.variables.get(&identifier.name);
-                match value {
-                    Some(value) => Ok(value.clone()),
-                    None => Err(RuntimeError::UndefinedVariable(
-                        format!("{}", identifier.name).to_string(),
-                        self.context.current_line,
-                    )),
-                }
-            }
+            Expression::Identifier(identifier) => self.visit_identifier(identifier),
             Expression::Literal(literal) => self.visit_literal(literal),
             Expression::UnaryExpression(unary) => self.visit_unary_expression(unary),
             Expression::BinaryExpression(binary) => self.visit_binary_expression(binary),
+            Expression::RelationExpression(relation) => self.visit_relation_expression(relation),
+            Expression::CallExpression(call) => self.visit_call_expression(call),
             _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
         }
     }
 
+    fn visit_identifier(&self, identifier: &Identifier) -> InterpreterResult {
+        match identifier.name.as_str() {
+            "ERR" => Ok(Value::Number(
+                self.context.error.as_ref().map_or(0, |error| error.code) as f32,
+            )),
+            "ERL" => Ok(Value::Number(
+                self.context.error.as_ref().map_or(0, |error| error.line) as f32,
+            )),
+            "TIMER" => Ok(Value::Number(
+                self.context.clock.now().seconds_since_midnight(),
+            )),
+            "SIZE" => Ok(Value::Number(self.free_memory() as f32)),
+            "DATE$" => Ok(Value::String(self.context.clock.now().date())),
+            "TIME$" => Ok(Value::String(self.context.clock.now().time())),
+            "COMMAND$" => Ok(Value::String(command_arguments().join(" "))),
+            "ARGC" => Ok(Value::Number(command_arguments().len() as f32)),
+            "INKEY$" => {
+                let key = self.context.events.keys.borrow_mut().pop_front();
+                if key.is_some() {
+                    let handled = &self.context.events.keys_handled;
+                    handled.set(handled.get().saturating_sub(1));
+                }
+                // The terminal only leaves line mode once a program reads keys
+                let key = key.or_else(|| {
+                    set_raw_mode(true);
+                    poll_key()
+                });
+                self.context.events.idle.set(key.is_none());
+
+                Ok(Value::String(key.map(String::from).unwrap_or_default()))
+            }
+            name => match self.context.variables.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeError::UndefinedVariable(
+                    name.to_string(),
+                    self.context.current_line,
+                )),
+            },
+        }
+    }
+
+    fn visit_call_expression(&self, call: &CallExpression) -> InterpreterResult {
+        let mut arguments: Vec<Value> = vec![];
+        for argument in &call.arguments {
+            arguments.push(self.visit_expression(argument)?);
+        }
+
+        let name = call.callee.name.as_str();
+        let illegal_call =
+            || RuntimeError::IllegalFunctionCall(name.to_string(), self.context.current_line);
+
+        if let Some(element) = self.get_element(name, &arguments) {
+            return element;
+        }
+
+        match name {
+            "ABS" => match arguments.as_slice() {
+                [Value::Number(x)] => Ok(Value::Number(x.abs())),
+                _ => Err(illegal_call()),
+            },
+            "INT" => match arguments.as_slice() {
+                [Value::Number(x)] => Ok(Value::Number(x.floor())),
+                _ => Err(illegal_call()),
+            },
+            "RND" => match arguments.as_slice() {
+                [] => Ok(Value::Number(self.context.random.next())),
+                [Value::Number(x)] => Ok(Value::Number(self.context.random.rnd(*x))),
+                _ => Err(illegal_call()),
+            },
+            "EOF" => match arguments.as_slice() {
+                [Value::Number(number)] => {
+                    let channel = self.context.files.get(*number as usize)?;
+                    Ok(Value::Number(if channel.eof() { -1.0 } else { 0.0 }))
+                }
+                _ => Err(illegal_call()),
+            },
+            "MKI$" => match arguments.as_slice() {
+                [Value::Number(number)] if (i16::MIN as f32..=i16::MAX as f32).contains(number) => {
+                    Ok(Value::String(encode_bytes(&(*number as i16).to_le_bytes())))
+                }
+                _ => Err(illegal_call()),
+            },
+            "MKS$" => match arguments.as_slice() {
+                [Value::Number(number)] => Ok(Value::String(encode_bytes(&number.to_le_bytes()))),
+                _ => Err(illegal_call()),
+            },
+            "CVI" => match arguments.as_slice() {
+                [Value::String(string)] => match decode_bytes::<2>(string) {
+                    Some(bytes) => Ok(Value::Number(i16::from_le_bytes(bytes) as f32)),
+                    None => Err(illegal_call()),
+                },
+                _ => Err(illegal_call()),
+            },
+            "CVS" => match arguments.as_slice() {
+                [Value::String(string)] => match decode_bytes::<4>(string) {
+                    Some(bytes) => Ok(Value::Number(f32::from_le_bytes(bytes))),
+                    None => Err(illegal_call()),
+                },
+                _ => Err(illegal_call()),
+            },
+            // Strings are measured and indexed in Unicode scalar values, never bytes
+            "LEN" => match arguments.as_slice() {
+                [Value::String(string)] => Ok(Value::Number(string.chars().count() as f32)),
+                _ => Err(illegal_call()),
+            },
+            "LEFT$" => match arguments.as_slice() {
+                [Value::String(string), Value::Number(count)] if *count >= 0.0 => Ok(
+                    Value::String(string.chars().take(*count as usize).collect()),
+                ),
+                _ => Err(illegal_call()),
+            },
+            "RIGHT$" => match arguments.as_slice() {
+                [Value::String(string), Value::Number(count)] if *count >= 0.0 => {
+                    let skip = string.chars().count().saturating_sub(*count as usize);
+                    Ok(Value::String(string.chars().skip(skip).collect()))
+                }
+                _ => Err(illegal_call()),
+            },
+            "MID$" => match arguments.as_slice() {
+                [Value::String(string), Value::Number(start), rest @ ..] if *start >= 1.0 => {
+                    let count = match rest {
+                        [] => usize::MAX,
+                        [Value::Number(count)] if *count >= 0.0 => *count as usize,
+                        _ => return Err(illegal_call()),
+                    };
+
+                    Ok(Value::String(
+                        string
+                            .chars()
+                            .skip(*start as usize - 1)
+                            .take(count)
+                            .collect(),
+                    ))
+                }
+                _ => Err(illegal_call()),
+            },
+            "INSTR" => {
+                let (start, haystack, needle) = match arguments.as_slice() {
+                    [Value::String(haystack), Value::String(needle)] => (1, haystack, needle),
+                    [Value::Number(start), Value::String(haystack), Value::String(needle)]
+                        if *start >= 1.0 =>
+                    {
+                        (*start as usize, haystack, needle)
+                    }
+                    _ => return Err(illegal_call()),
+                };
+
+                let haystack: Vec<char> = haystack.chars().collect();
+                let needle: Vec<char> = needle.chars().collect();
+                let position = (start - 1..=haystack.len().saturating_sub(needle.len()))
+                    .find(|index| haystack[*index..].starts_with(&needle))
+                    .map_or(0, |index| index + 1);
+
+                Ok(Value::Number(position as f32))
+            }
+            "ASC" => match arguments.as_slice() {
+                [Value::String(string)] => match string.chars().next() {
+                    Some(c) => Ok(Value::Number(c as u32 as f32)),
+                    None => Err(illegal_call()),
+                },
+                _ => Err(illegal_call()),
+            },
+            "CHR$" => match arguments.as_slice() {
+                [Value::Number(code)] if *code >= 0.0 => match char::from_u32(*code as u32) {
+                    Some(c) => Ok(Value::String(c.to_string())),
+                    None => Err(illegal_call()),
+                },
+                _ => Err(illegal_call()),
+            },
+            "STR$" => match arguments.as_slice() {
+                [Value::Number(number)] => Ok(Value::String(number.to_string())),
+                _ => Err(illegal_call()),
+            },
+            "VAL" => match arguments.as_slice() {
+                [Value::String(string)] => Ok(Value::Number(string.trim().parse().unwrap_or(0.0))),
+                _ => Err(illegal_call()),
+            },
+            "SHL" | "SHR" => match arguments.as_slice() {
+                [Value::Number(value), Value::Number(bits)] if *bits >= 0.0 => {
+                    let value = self.to_integer(*value)?;
+                    let bits = *bits as u32;
+                    let result = match name {
+                        "SHL" => value.checked_shl(bits),
+                        _ => value.checked_shr(bits),
+                    };
+                    Ok(Value::Number(from_integer(result.unwrap_or(0))))
+                }
+                _ => Err(illegal_call()),
+            },
+            "ARGV$" => match arguments.as_slice() {
+                [Value::Number(index)] if *index >= 1.0 => {
+                    match command_arguments().get(*index as usize - 1) {
+                        Some(argument) => Ok(Value::String(argument.clone())),
+                        None => Err(illegal_call()),
+                    }
+                }
+                _ => Err(illegal_call()),
+            },
+            "ENVIRON$" => match arguments.as_slice() {
+                [Value::String(name)] => Ok(Value::String(environment(name).unwrap_or_default())),
+                _ => Err(illegal_call()),
+            },
+            "HEX$" => match arguments.as_slice() {
+                [Value::Number(value)] => {
+                    Ok(Value::String(format!("{:X}", self.to_integer(*value)?)))
+                }
+                _ => Err(illegal_call()),
+            },
+            "BIN$" => match arguments.as_slice() {
+                [Value::Number(value)] => {
+                    Ok(Value::String(format!("{:b}", self.to_integer(*value)?)))
+                }
+                _ => Err(illegal_call()),
+            },
+            "PEEK" => match arguments.as_slice() {
+                [Value::Number(address)] => Ok(Value::Number(
+                    self.peek(self.to_address(*address, name)?) as f32,
+                )),
+                _ => Err(illegal_call()),
+            },
+            "USR" => match arguments.as_slice() {
+                [Value::Number(address), rest @ ..] => {
+                    let mut numbers: Vec<f32> = vec![];
+                    for argument in rest {
+                        match argument {
+                            Value::Number(number) => numbers.push(*number),
+                            _ => return Err(illegal_call()),
+                        }
+                    }
+
+                    match self
+                        .context
+                        .memory
+                        .call(self.to_address(*address, name)?, &numbers)
+                    {
+                        Some(result) => Ok(Value::Number(result)),
+                        None => Err(illegal_call()),
+                    }
+                }
+                _ => Err(illegal_call()),
+            },
+            _ => Err(RuntimeError::UndefinedFunction(
+                name.to_string(),
+                self.context.current_line,
+            )),
+        }
+    }
+
+    fn to_address(&self, address: f32, name: &str) -> Result<u16, RuntimeError> {
+        if address < 0.0 || address >= MEMORY_SIZE as f32 {
+            return Err(RuntimeError::IllegalFunctionCall(
+                name.to_string(),
+                self.context.current_line,
+            ));
+        }
+
+        Ok(address as u16)
+    }
+
+    fn system_word(&self, address: u16) -> Option<usize> {
+        let program_end = PROGRAM_START + self.context.program.size();
+
+        match address & !1 {
+            SYSTEM_PROGRAM_START => Some(PROGRAM_START),
+            SYSTEM_MEMORY_END => Some(MEMORY_SIZE - 1),
+            SYSTEM_PROGRAM_END => Some(program_end.min(MEMORY_SIZE - 1)),
+            _ => None,
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        // Words are stored little endian, low byte first
+        match self.system_word(address) {
+            Some(word) if address & 1 == 0 => (word & 0xff) as u8,
+            Some(word) => (word >> 8) as u8,
+            None => self.context.memory.peek(address),
+        }
+    }
+
     fn visit_literal(&self, literal: &Literal) -> InterpreterResult {
         match literal {
             Literal::Number { value } => Ok(Value::Number(*value)),
@@ -103,7 +635,66 @@ impl Interpreter {
         }
     }
 
+    // Integers are 16 bits as in MS BASIC, -32768 to 65535 share the same bit patterns
+    fn to_integer(&self, number: f32) -> Result<u16, RuntimeError> {
+        let number = number.round();
+        if !(i16::MIN as f32..=u16::MAX as f32).contains(&number) {
+            return Err(RuntimeError::Overflow(self.context.current_line));
+        }
+
+        Ok(number as i32 as u16)
+    }
+
+    // Bitwise operators and relations nested under them stay integers until the result
+    fn visit_integer_expression(&self, expression: &Expression) -> Result<u16, RuntimeError> {
+        let integer = match expression {
+            Expression::BinaryExpression(binary) => self.visit_integer_binary_expression(binary)?,
+            Expression::UnaryExpression(UnaryExpression {
+                operator: Some(UnaryOperator::Not),
+                argument,
+            }) => Some(!self.visit_integer_expression(argument)?),
+            Expression::RelationExpression(relation) => {
+                let left = self.visit_expression(&relation.left)?;
+                let right = self.visit_expression(&relation.right)?;
+                match self.compare(&relation.operator, left, right)? {
+                    true => Some(u16::MAX),
+                    false => Some(0),
+                }
+            }
+            _ => None,
+        };
+
+        match integer {
+            Some(integer) => Ok(integer),
+            None => match self.visit_expression(expression)? {
+                Value::Number(number) => self.to_integer(number),
+                _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
+            },
+        }
+    }
+
+    fn visit_integer_binary_expression(
+        &self,
+        binary: &BinaryExpression,
+    ) -> Result<Option<u16>, RuntimeError> {
+        let operation: fn(u16, u16) -> u16 = match binary.operator {
+            ArithmeticOperator::And => |left, right| left & right,
+            ArithmeticOperator::Or => |left, right| left | right,
+            ArithmeticOperator::Xor => |left, right| left ^ right,
+            _ => return Ok(None),
+        };
+
+        let left = self.visit_integer_expression(&binary.left)?;
+        let right = self.visit_integer_expression(&binary.right)?;
+
+        Ok(Some(operation(left, right)))
+    }
+
     fn visit_binary_expression(&self, binary: &BinaryExpression) -> InterpreterResult {
+        if let Some(integer) = self.visit_integer_binary_expression(binary)? {
+            return Ok(Value::Number(from_integer(integer)));
+        }
+
         let left = self.visit_expression(&binary.left)?;
         let right = self.visit_expression(&binary.right)?;
 
@@ -114,6 +705,9 @@ impl Interpreter {
                     ArithmeticOperator::Subtract => left - right,
                     ArithmeticOperator::Multiply => left * right,
                     ArithmeticOperator::Divide => left / right,
+                    ArithmeticOperator::And | ArithmeticOperator::Or | ArithmeticOperator::Xor => {
+                        unreachable!("bitwise operators are evaluated as integers")
+                    }
                 };
 
                 Ok(Value::Number(result))
@@ -126,18 +720,50 @@ impl Interpreter {
 
                 Ok(Value::String(result))
             }
+            // Mixed concatenation formats the number, unless OPTION STRICT is on
+            (Value::String(left), Value::Number(right))
+                if binary.operator == ArithmeticOperator::Add =>
+            {
+                match self.context.options.strict {
+                    true => Err(RuntimeError::TypeMismatch(self.context.current_line)),
+                    false => Ok(Value::String(left + &right.to_string())),
+                }
+            }
+            (Value::Number(left), Value::String(right))
+                if binary.operator == ArithmeticOperator::Add =>
+            {
+                match self.context.options.strict {
+                    true => Err(RuntimeError::TypeMismatch(self.context.current_line)),
+                    false => Ok(Value::String(left.to_string() + &right)),
+                }
+            }
             _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
         }
     }
 
+    fn visit_relation_expression(&self, relation: &RelationExpression) -> InterpreterResult {
+        let left = self.visit_expression(&relation.left)?;
+        let right = self.visit_expression(&relation.right)?;
+
+        // True is -1 so that AND, OR and NOT also work as logical operators
+        match self.compare(&relation.operator, left, right)? {
+            true => Ok(Value::Number(-1.0)),
+            false => Ok(Value::Number(0.0)),
+        }
+    }
+
     fn visit_unary_expression(&self, unary: &UnaryExpression) -> InterpreterResult {
+        if unary.operator == Some(UnaryOperator::Not) {
+            let integer = self.visit_integer_expression(&unary.argument)?;
+            return Ok(Value::Number(from_integer(!integer)));
+        }
+
         let value = self.visit_expression(&unary.argument)?;
 
         match value {
             Value::Number(number) => match unary.operator {
-                Some(UnaryOperator::Plus) => Ok(Value::Number(number)),
                 Some(UnaryOperator::Minus) => Ok(Value::Number(-number)),
-                None => Ok(Value::Number(number)),
+                _ => Ok(Value::Number(number)),
             },
             _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
         }
@@ -158,108 +784,1451 @@ impl Interpreter {
         Ok(Value::String(results.join(" ")))
     }
 
+    fn compare(
+        &self,
+        operator: &RelationOperator,
+        left: Value,
+        right: Value,
+    ) -> Result<bool, RuntimeError> {
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => Ok(match operator {
+                RelationOperator::Equal => left == right,
+                RelationOperator::NotEqual => left != right,
+                RelationOperator::LessThan => left < right,
+                RelationOperator::LessThanOrEqual => left <= right,
+                RelationOperator::GreaterThan => left > right,
+                RelationOperator::GreaterThanOrEqual => left >= right,
+            }),
+            (Value::String(left), Value::String(right)) => {
+                // Ordinal compares code points, case-insensitive compares upper case forms
+                let ordering = match self.context.options.collation {
+                    Collation::Ordinal => left.cmp(&right),
+                    Collation::CaseInsensitive => left.to_uppercase().cmp(&right.to_uppercase()),
+                };
+
+                Ok(match operator {
+                    RelationOperator::Equal => ordering.is_eq(),
+                    RelationOperator::NotEqual => ordering.is_ne(),
+                    RelationOperator::LessThan => ordering.is_lt(),
+                    RelationOperator::LessThanOrEqual => ordering.is_le(),
+                    RelationOperator::GreaterThan => ordering.is_gt(),
+                    RelationOperator::GreaterThanOrEqual => ordering.is_ge(),
+                })
+            }
+            _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        }
+    }
+
     async fn visit_if_statement(
         &mut self,
-        condition: &IfCondition,
+        condition: &Expression,
         then: &Box<Statement>,
     ) -> InterpreterResult {
-        let left = self.visit_expression(&condition.left)?;
-        let right = self.visit_expression(&condition.right)?;
+        let condition = match self.visit_expression(condition)? {
+            Value::Number(number) => number != 0.0,
+            _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        };
 
-        match (left, right) {
-            (Value::Number(left), Value::Number(right)) => {
-                let result = match condition.operator {
-                    RelationOperator::Equal => left == right,
-                    RelationOperator::NotEqual => left != right,
-                    RelationOperator::LessThan => left < right,
-                    RelationOperator::LessThanOrEqual => left <= right,
-                    RelationOperator::GreaterThan => left > right,
-                    RelationOperator::GreaterThanOrEqual => left >= right,
-                };
+        if condition {
+            Box::pin(self.visit_statement(then)).await
+        } else {
+            Ok(Value::None)
+        }
+    }
+
+    fn test_case(&self, value: &Value, tests: &Vec<CaseTest>) -> Result<bool, RuntimeError> {
+        for test in tests {
+            let matched = match test {
+                CaseTest::Value(expression) => {
+                    let expected = self.visit_expression(expression)?;
+                    self.compare(&RelationOperator::Equal, value.clone(), expected)?
+                }
+                CaseTest::Range { from, to } => {
+                    let from = self.visit_expression(from)?;
+                    let to = self.visit_expression(to)?;
+                    self.compare(&RelationOperator::GreaterThanOrEqual, value.clone(), from)?
+                        && self.compare(&RelationOperator::LessThanOrEqual, value.clone(), to)?
+                }
+                CaseTest::Is {
+                    operator,
+                    value: expected,
+                } => {
+                    let expected = self.visit_expression(expected)?;
+                    self.compare(operator, value.clone(), expected)?
+                }
+            };
+
+            if matched {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn find_end_select(&self) -> Result<usize, RuntimeError> {
+        let mut depth = 0;
+        for number in self.context.current_line..self.context.program.lines.len() {
+            match self.context.program.lines[number]
+                .as_ref()
+                .map(|line| &line.statement)
+            {
+                Some(Statement::SelectStatement { .. }) => depth += 1,
+                Some(Statement::EndSelectStatement) if depth > 0 => depth -= 1,
+                Some(Statement::EndSelectStatement) => return Ok(number),
+                _ => {}
+            }
+        }
+
+        Err(RuntimeError::InvalidState(String::from(
+            "SELECT CASE without END SELECT",
+        )))
+    }
+
+    fn visit_select_statement(&mut self, discriminant: &Expression) -> InterpreterResult {
+        if !matches!(self.state, InterpreterState::Running) {
+            return Err(RuntimeError::InvalidState(String::from(
+                "SELECT CASE is only allowed inside a program",
+            )));
+        }
 
-                if result {
-                    Box::pin(self.visit_statement(&then)).await
-                } else {
-                    Ok(Value::None)
+        let value = self.visit_expression(discriminant)?;
+        let end = self.find_end_select()?;
+
+        // Jump to the first CASE that matches, skipping any nested SELECT block
+        let mut depth = 0;
+        for number in self.context.current_line..end {
+            match self.context.program.lines[number]
+                .as_ref()
+                .map(|line| &line.statement)
+            {
+                Some(Statement::SelectStatement { .. }) => depth += 1,
+                Some(Statement::EndSelectStatement) => depth -= 1,
+                Some(Statement::CaseStatement { tests })
+                    if depth == 0 && self.test_case(&value, tests)? =>
+                {
+                    self.context.current_line = number + 1;
+                    return Ok(Value::None);
+                }
+                Some(Statement::CaseElseStatement) if depth == 0 => {
+                    self.context.current_line = number + 1;
+                    return Ok(Value::None);
                 }
+                _ => {}
             }
-            (Value::String(left), Value::String(right)) => {
-                let result = match condition.operator {
-                    RelationOperator::Equal => left == right,
-                    RelationOperator::NotEqual => left != right,
-                    _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        }
+
+        self.context.current_line = end + 1;
+
+        Ok(Value::None)
+    }
+
+    fn visit_case_statement(&mut self) -> InterpreterResult {
+        if !matches!(self.state, InterpreterState::Running) {
+            return Err(RuntimeError::InvalidState(String::from(
+                "CASE is only allowed inside a program",
+            )));
+        }
+
+        // Reaching a CASE means the previous branch is over
+        self.context.current_line = self.find_end_select()? + 1;
+
+        Ok(Value::None)
+    }
+
+    async fn visit_run_statement(&mut self) -> InterpreterResult {
+        self.reset()?;
+        self.prepare_run()?;
+
+        self.run().await
+    }
+
+    // Runs each subroutine whose first line is REM TEST_<name> on its own
+    async fn visit_test_statement(&mut self) -> InterpreterResult {
+        if matches!(self.state, InterpreterState::Running) {
+            return Err(RuntimeError::InvalidState(String::from(
+                "TEST is only available in direct mode",
+            )));
+        }
+
+        // The label is a REM line or the trailing comment of the first line
+        let tests: Vec<(usize, String)> = (0..self.context.program.lines.len())
+            .filter_map(|number| {
+                let line = self.context.program.get(number)?;
+                let text = match (line.statement, line.comment) {
+                    (Statement::RemStatement { text }, _) => text,
+                    (_, Some(comment)) => comment,
+                    _ => return None,
                 };
 
-                if result {
-                    Box::pin(self.visit_statement(&then)).await
-                } else {
-                    Ok(Value::None)
-                }
-            }
-            _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+                text.split_whitespace()
+                    .next()
+                    .filter(|name| name.starts_with("TEST_"))
+                    .map(|name| (number, name.to_string()))
+            })
+            .collect();
+
+        let mut passed = 0;
+        let mut failed = 0;
+        for (number, name) in tests {
+            self.reset()?;
+            self.prepare_run()?;
+            self.context.current_line = number;
+            // The final RETURN lands past the end of the program
+            self.context.stack.push(self.context.program.lines.len());
+
+            // Only a test that returns passes, STOP or END part way through fails
+            let outcome = match self.run().await {
+                Ok(_) if matches!(self.state, InterpreterState::Paused) => {
+                    Err(format!("stopped at line {}", self.context.current_line - 1))
+                }
+                Ok(_) if !self.context.stack.is_empty() => {
+                    Err(String::from("ended without RETURN"))
+                }
+                Ok(_) => Ok(()),
+                Err(error) => Err(error.to_string()),
+            };
+
+            match outcome {
+                Ok(()) => {
+                    passed += 1;
+                    write_line(format!("{} ... ok", name).as_str());
+                }
+                Err(reason) => {
+                    // Report the failure itself, not a flush error behind it
+                    self.stop().ok();
+                    failed += 1;
+                    write_line(format!("{} ... FAILED: {}", name, reason).as_str());
+                }
+            }
+        }
+
+        Ok(Value::String(format!(
+            "{} passed, {} failed",
+            passed, failed
+        )))
+    }
+
+    async fn run(&mut self) -> InterpreterResult {
+        self.state = InterpreterState::Running;
+
+        while matches!(self.state, InterpreterState::Running) {
+            if self.context.current_line >= self.context.program.lines.len() {
+                // The program ends once every task, the main one included, has run out
+                if self.switch_task(false) {
+                    continue;
+                }
+                break;
+            }
+
+            self.dispatch_events();
+
+            let number = self.context.current_line;
+            let line = self.context.program.get(number);
+            self.context.current_line += 1;
+            match line {
+                Some(line) => {
+                    if self.context.trace != TraceMode::Off {
+                        write(format!("[{}]", number).as_str());
+                    }
+
+                    if let Err(error) = self.execute_line(line, number).await {
+                        return Err(match self.context.included.get(&number) {
+                            Some(file) => RuntimeError::Included(file.clone(), Box::new(error)),
+                            None => error,
+                        });
+                    }
+
+                    if self.context.events.idle.replace(false) {
+                        yield_now().await;
+                    }
+                }
+                None => {}
+            };
+        }
+
+        if matches!(self.state, InterpreterState::Running) {
+            self.stop()?;
+        }
+        set_raw_mode(false);
+
+        Ok(Value::None)
+    }
+
+    // Resumes the next suspended task, keeping the running one queued unless it ended
+    fn switch_task(&mut self, keep_current: bool) -> bool {
+        let next = match self.context.tasks.suspended.pop_front() {
+            Some(next) => next,
+            None => return false,
+        };
+
+        if keep_current {
+            let current = Task {
+                id: self.context.tasks.current,
+                parent: self.context.tasks.parent,
+                current_line: self.context.current_line,
+                stack: std::mem::take(&mut self.context.stack),
+                handler_depth: self.context.events.handler_depth,
+            };
+            self.context.tasks.suspended.push_back(current);
+        }
+
+        self.context.tasks.current = next.id;
+        self.context.tasks.parent = next.parent;
+        self.context.current_line = next.current_line;
+        self.context.stack = next.stack;
+        self.context.events.handler_depth = next.handler_depth;
+        true
+    }
+
+    fn stop(&mut self) -> Result<(), RuntimeError> {
+        self.state = InterpreterState::Stopped;
+        set_raw_mode(false);
+        self.context.files.close_all()
+    }
+
+    async fn execute_line(&mut self, line: Line, number: usize) -> Result<(), RuntimeError> {
+        match self.visit_statement(&line.statement).await {
+            Ok(value) => {
+                if value != Value::None {
+                    write_line(format!("{}", value).as_str());
+                }
+            }
+            Err(error) => {
+                if let Err(error) = self.trap_error(error, number) {
+                    self.stop().ok();
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_randomize_statement(&mut self, seed: &Option<Expression>) -> InterpreterResult {
+        let seed = match seed {
+            Some(seed) => match self.visit_expression(seed)? {
+                Value::Number(number) => number,
+                _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+            },
+            None => self.context.clock.now().seconds_since_midnight(),
+        };
+
+        self.context.random.seed(seed);
+
+        Ok(Value::None)
+    }
+
+    async fn sleep(&self, duration: &Expression, scale: f32) -> InterpreterResult {
+        let milliseconds = match self.visit_expression(duration)? {
+            Value::Number(number) if number >= 0.0 => number * scale,
+            _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        };
+
+        self.context.clock.sleep(milliseconds as u64).await;
+
+        Ok(Value::None)
+    }
+
+    fn dispatch_events(&mut self) {
+        // Without KEY ON typed-ahead input stays buffered for INPUT
+        if matches!(&self.context.events.key, Some(key) if key.enabled) {
+            while let Some(key) = poll_key() {
+                self.context.events.keys.get_mut().push_back(key);
+            }
+        }
+
+        // A task suspended inside a handler still blocks the others from starting one
+        let tasks = &self.context.tasks.suspended;
+        if self.context.events.handler_depth.is_some()
+            || tasks.iter().any(|task| task.handler_depth.is_some())
+        {
+            return;
+        }
+
+        let now = self.context.clock.now().seconds_since_midnight();
+        let mut handler = None;
+
+        if let Some(timer) = self.context.events.timer.as_mut() {
+            // Wrap around midnight
+            let elapsed = (now - timer.last).rem_euclid(86_400.0);
+            if timer.enabled && elapsed >= timer.interval {
+                timer.last = now;
+                handler = Some(timer.handler);
+            }
+        }
+
+        if let Some(key) = self.context.events.key.as_ref() {
+            let handled = self.context.events.keys_handled.get();
+            if handler.is_none() && key.enabled && self.context.events.keys.borrow().len() > handled
+            {
+                self.context.events.keys_handled.set(handled + 1);
+                handler = Some(key.handler);
+            }
+        }
+
+        if let Some(handler) = handler {
+            self.context.events.handler_depth = Some(self.context.stack.len());
+            self.context.stack.push(self.context.current_line);
+            self.context.current_line = handler;
+        }
+    }
+
+    fn visit_on_timer_statement(
+        &mut self,
+        interval: &Expression,
+        location: &Expression,
+    ) -> InterpreterResult {
+        let interval = match self.visit_expression(interval)? {
+            Value::Number(number) if number > 0.0 => number,
+            _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        };
+        let handler = self.visit_line_number(location)?;
+
+        self.context.events.timer = Some(TimerEvent {
+            interval,
+            handler,
+            enabled: false,
+            last: 0.0,
+        });
+
+        Ok(Value::None)
+    }
+
+    fn visit_on_key_statement(&mut self, location: &Expression) -> InterpreterResult {
+        let handler = self.visit_line_number(location)?;
+
+        self.context.events.key = Some(KeyEvent {
+            handler,
+            enabled: false,
+        });
+
+        Ok(Value::None)
+    }
+
+    fn visit_timer_statement(&mut self, enabled: bool) -> InterpreterResult {
+        let now = self.context.clock.now().seconds_since_midnight();
+
+        match self.context.events.timer.as_mut() {
+            Some(timer) => {
+                timer.enabled = enabled;
+                timer.last = now;
+                Ok(Value::None)
+            }
+            None => Err(RuntimeError::InvalidState(String::from(
+                "TIMER ON without ON TIMER",
+            ))),
+        }
+    }
+
+    fn visit_key_statement(&mut self, enabled: bool) -> InterpreterResult {
+        match self.context.events.key.as_mut() {
+            Some(key) => {
+                key.enabled = enabled;
+                if enabled {
+                    set_raw_mode(true);
+                }
+                Ok(Value::None)
+            }
+            None => Err(RuntimeError::InvalidState(String::from(
+                "KEY ON without ON KEY",
+            ))),
+        }
+    }
+
+    fn visit_option_statement(&mut self, directive: &OptionDirective) -> InterpreterResult {
+        let options = &mut self.context.options;
+        match directive {
+            OptionDirective::Compare(collation) => options.collation = *collation,
+            OptionDirective::Base(base) => options.base = *base,
+            OptionDirective::Explicit => options.explicit = true,
+            OptionDirective::Strict => options.strict = true,
+        }
+
+        Ok(Value::None)
+    }
+
+    // OPTION applies to the whole program wherever it appears
+    // Libraries go in before the pre-pass, so it also sees their OPTION lines
+    fn prepare_run(&mut self) -> Result<(), RuntimeError> {
+        self.expand_includes()?;
+        self.collect_options()
+    }
+
+    // Only literal paths, a computed one needs the variables of the run
+    fn expand_includes(&mut self) -> Result<(), RuntimeError> {
+        // Lines from the last RUN go first, so edited libraries are read afresh
+        for number in self.context.included.drain().map(|(number, _)| number) {
+            self.context.program.lines[number] = None;
+        }
+
+        let paths: Vec<Expression> = self
+            .context
+            .program
+            .iter()
+            .filter_map(|line| match line.statement {
+                Statement::IncludeStatement {
+                    path: path @ Expression::Literal(Literal::String { .. }),
+                } => Some(path),
+                _ => None,
+            })
+            .collect();
+        for path in &paths {
+            self.visit_include_statement(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn collect_options(&mut self) -> Result<(), RuntimeError> {
+        self.context.options = Options::default();
+
+        let directives: Vec<OptionDirective> = self
+            .context
+            .program
+            .iter()
+            .filter_map(|line| match line.statement {
+                Statement::OptionStatement { directive } => Some(directive),
+                _ => None,
+            })
+            .collect();
+        for directive in &directives {
+            self.visit_option_statement(directive)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_tron_statement(&mut self, variables: bool) -> InterpreterResult {
+        self.context.trace = if variables {
+            TraceMode::Variables
+        } else {
+            TraceMode::Lines
+        };
+
+        Ok(Value::None)
+    }
+
+    fn visit_troff_statement(&mut self) -> InterpreterResult {
+        self.context.trace = TraceMode::Off;
+
+        Ok(Value::None)
+    }
+
+    fn visit_stop_statement(&mut self) -> InterpreterResult {
+        if !matches!(self.state, InterpreterState::Running) {
+            return Ok(Value::None);
+        }
+
+        self.state = InterpreterState::Paused;
+
+        Ok(Value::String(format!(
+            "Break in line {}",
+            self.context.current_line - 1
+        )))
+    }
+
+    async fn visit_cont_statement(&mut self) -> InterpreterResult {
+        if !matches!(self.state, InterpreterState::Paused) {
+            return Err(RuntimeError::CantContinue);
+        }
+
+        self.run().await
+    }
+
+    fn trap_error(&mut self, error: RuntimeError, line: usize) -> Result<(), RuntimeError> {
+        // Errors raised while a handler is running are fatal
+        match self.context.error_handler {
+            Some(handler) if self.context.error.is_none() => {
+                self.context.error = Some(TrappedError {
+                    code: error.code(),
+                    line,
+                });
+                self.context.current_line = handler;
+
+                Ok(())
+            }
+            _ => Err(error),
+        }
+    }
+
+    fn visit_line_number(&self, location: &Expression) -> Result<usize, RuntimeError> {
+        match self.visit_expression(location)? {
+            Value::Number(number) => Ok(number as usize),
+            value => Err(RuntimeError::IllegalLineNumber(format!("{}", value))),
+        }
+    }
+
+    fn visit_on_error_statement(&mut self, location: &Expression) -> InterpreterResult {
+        let location = self.visit_line_number(location)?;
+
+        // ON ERROR GOTO 0 turns error trapping off
+        self.context.error_handler = match location {
+            0 => None,
+            location => Some(location),
+        };
+
+        Ok(Value::None)
+    }
+
+    fn visit_resume_statement(&mut self, target: &ResumeTarget) -> InterpreterResult {
+        let line = match &self.context.error {
+            Some(error) => error.line,
+            None => return Err(RuntimeError::ResumeWithoutError),
+        };
+
+        self.context.current_line = match target {
+            ResumeTarget::Retry => line,
+            ResumeTarget::Next => line + 1,
+            ResumeTarget::Line(location) => match self.visit_expression(location)? {
+                Value::Number(number) => number as usize,
+                value => return Err(RuntimeError::IllegalLineNumber(format!("{}", value))),
+            },
+        };
+        self.context.error = None;
+
+        Ok(Value::None)
+    }
+
+    fn trace_assignment(&self, target: &str, value: &Value) {
+        if self.context.trace == TraceMode::Variables
+            && matches!(self.state, InterpreterState::Running)
+        {
+            let value = match value {
+                Value::String(string) => format!("\"{}\"", string),
+                value => format!("{}", value),
+            };
+            write(format!("[{} {}={}]", self.context.current_line - 1, target, value).as_str());
+        }
+    }
+
+    // Under OPTION STRICT only names ending in $ hold strings
+    fn check_type(&self, name: &str, value: &Value) -> Result<(), RuntimeError> {
+        match (name.ends_with('$'), value) {
+            (true, Value::Number(_)) | (false, Value::String(_)) if self.context.options.strict => {
+                Err(RuntimeError::TypeMismatch(self.context.current_line))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn set_variable(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        let line = self.context.current_line;
+        if self.context.constants.contains(name) {
+            return Err(RuntimeError::AssignmentToConstant(name.to_string(), line));
+        }
+        if self.context.options.explicit && !self.context.variables.contains_key(name) {
+            return Err(RuntimeError::VariableNotDeclared(name.to_string(), line));
+        }
+
+        self.check_type(name, &value)?;
+        self.trace_assignment(name, &value);
+
+        self.context.variables.insert(name.to_string(), value);
+
+        Ok(())
+    }
+
+    fn new_array(&self, name: &str, bounds: &Vec<Expression>) -> Result<Array, RuntimeError> {
+        let mut values: Vec<Value> = vec![];
+        for bound in bounds {
+            values.push(self.visit_expression(bound)?);
+        }
+
+        let initial = if name.ends_with('$') {
+            Value::String(String::new())
+        } else {
+            Value::Number(0.0)
+        };
+
+        Array::with_bounds(
+            &self.to_indices(&values)?,
+            self.context.options.base,
+            initial,
+        )
+        .ok_or(RuntimeError::SubscriptOutOfRange(self.context.current_line))
+    }
+
+    fn to_indices(&self, values: &[Value]) -> Result<Vec<usize>, RuntimeError> {
+        let mut indices: Vec<usize> = vec![];
+        for value in values {
+            match value {
+                Value::Number(number) if *number >= 0.0 => indices.push(*number as usize),
+                _ => return Err(RuntimeError::SubscriptOutOfRange(self.context.current_line)),
+            }
+        }
+
+        Ok(indices)
+    }
+
+    fn free_memory(&self) -> usize {
+        MEMORY_SIZE - (PROGRAM_START + self.context.program.size()).min(MEMORY_SIZE)
+    }
+
+    // The Palo Alto @() array takes up whatever memory the program leaves free
+    fn palo_alto_array(&self) -> Array {
+        Array::new(vec![self.free_memory() / 2], Value::Number(0.0))
+    }
+
+    fn get_element(&self, name: &str, indices: &[Value]) -> Option<InterpreterResult> {
+        if name != "@" && !self.context.arrays.contains_key(name) {
+            return None;
+        }
+
+        let indices = match self.to_indices(indices) {
+            Ok(indices) => indices,
+            Err(error) => return Some(Err(error)),
+        };
+
+        let element = match self.context.arrays.get(name) {
+            Some(array) => array
+                .offset(&indices)
+                .map(|offset| array.values[offset].clone()),
+            // An unset @() reads as zero, so only the bound needs checking
+            None => match indices.as_slice() {
+                [index] if *index < self.free_memory() / 2 => Some(Value::Number(0.0)),
+                _ => None,
+            },
+        };
+
+        Some(element.ok_or(RuntimeError::SubscriptOutOfRange(self.context.current_line)))
+    }
+
+    fn set_element(
+        &mut self,
+        name: &str,
+        indices: &[Value],
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        let indices = self.to_indices(indices)?;
+
+        if name == "@" && !self.context.arrays.contains_key(name) {
+            let array = self.palo_alto_array();
+            self.context.arrays.insert(name.to_string(), array);
+        }
+
+        self.check_type(name, &value)?;
+        let subscripts: Vec<String> = indices.iter().map(|index| index.to_string()).collect();
+        self.trace_assignment(&format!("{}({})", name, subscripts.join(",")), &value);
+
+        let line = self.context.current_line;
+        let array = match self.context.arrays.get_mut(name) {
+            Some(array) => array,
+            None => return Err(RuntimeError::UndefinedVariable(name.to_string(), line)),
+        };
+
+        match array.offset(&indices) {
+            Some(offset) => {
+                array.values[offset] = value;
+                Ok(())
+            }
+            None => Err(RuntimeError::SubscriptOutOfRange(line)),
+        }
+    }
+
+    fn visit_dim_statement(&mut self, declarations: &Vec<ArrayDeclaration>) -> InterpreterResult {
+        for declaration in declarations {
+            let name = &declaration.name;
+            let line = self.context.current_line;
+
+            if declaration.dimensions.is_empty() {
+                if self.context.variables.contains_key(name) {
+                    return Err(RuntimeError::DuplicateDefinition(name.to_string(), line));
+                }
+
+                let value = match name.ends_with('$') {
+                    true => Value::String(String::new()),
+                    false => Value::Number(0.0),
+                };
+                self.context.variables.insert(name.to_string(), value);
+                continue;
+            }
+
+            if self.context.arrays.contains_key(name) {
+                return Err(RuntimeError::DuplicateDefinition(name.to_string(), line));
+            }
+
+            let array = self.new_array(name, &declaration.dimensions)?;
+            self.context.arrays.insert(name.to_string(), array);
+        }
+
+        Ok(Value::None)
+    }
+
+    fn visit_const_statement(&mut self, name: &str, value: &Expression) -> InterpreterResult {
+        let line = self.context.current_line;
+        if self.context.variables.contains_key(name) {
+            return Err(RuntimeError::DuplicateDefinition(name.to_string(), line));
+        }
+
+        let value = self.visit_expression(value)?;
+        self.check_type(name, &value)?;
+
+        self.context.variables.insert(name.to_string(), value);
+        self.context.constants.insert(name.to_string());
+
+        Ok(Value::None)
+    }
+
+    fn visit_erase_statement(&mut self, arrays: &Vec<Identifier>) -> InterpreterResult {
+        for array in arrays {
+            if self.context.arrays.remove(&array.name).is_none() {
+                return Err(RuntimeError::UndefinedVariable(
+                    array.name.to_string(),
+                    self.context.current_line,
+                ));
+            }
+        }
+
+        Ok(Value::None)
+    }
+
+    fn swap_target<'a>(
+        &self,
+        target: &'a Expression,
+    ) -> Result<(&'a str, Option<Vec<Value>>), RuntimeError> {
+        match target {
+            Expression::Identifier(identifier) => Ok((&identifier.name, None)),
+            Expression::CallExpression(call) => {
+                let mut indices: Vec<Value> = vec![];
+                for argument in &call.arguments {
+                    indices.push(self.visit_expression(argument)?);
+                }
+
+                Ok((&call.callee.name, Some(indices)))
+            }
+            _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        }
+    }
+
+    // Fails exactly where set_variable or set_element would, without writing
+    fn check_assignment(
+        &self,
+        name: &str,
+        indices: &Option<Vec<Value>>,
+        value: &Value,
+    ) -> Result<(), RuntimeError> {
+        let line = self.context.current_line;
+        let indices = match indices {
+            Some(indices) => indices,
+            None => {
+                if self.context.constants.contains(name) {
+                    return Err(RuntimeError::AssignmentToConstant(name.to_string(), line));
+                }
+                if self.context.options.explicit && !self.context.variables.contains_key(name) {
+                    return Err(RuntimeError::VariableNotDeclared(name.to_string(), line));
+                }
+                return self.check_type(name, value);
+            }
+        };
+
+        match self.get_element(name, indices) {
+            Some(element) => element?,
+            None => return Err(RuntimeError::UndefinedVariable(name.to_string(), line)),
+        };
+        self.check_type(name, value)
+    }
+
+    fn visit_swap_statement(&mut self, left: &Expression, right: &Expression) -> InterpreterResult {
+        let (left_name, left_indices) = self.swap_target(left)?;
+        let (right_name, right_indices) = self.swap_target(right)?;
+
+        let read = |name: &str, indices: &Option<Vec<Value>>| match indices {
+            Some(indices) => self.get_element(name, indices).unwrap_or_else(|| {
+                Err(RuntimeError::UndefinedVariable(
+                    name.to_string(),
+                    self.context.current_line,
+                ))
+            }),
+            None => self.visit_identifier(&Identifier {
+                name: name.to_string(),
+            }),
+        };
+        let left_value = read(left_name, &left_indices)?;
+        let right_value = read(right_name, &right_indices)?;
+
+        if std::mem::discriminant(&left_value) != std::mem::discriminant(&right_value) {
+            return Err(RuntimeError::InvalidOperation(self.context.current_line));
+        }
+
+        // Both targets are validated first so a failing SWAP changes neither
+        self.check_assignment(left_name, &left_indices, &right_value)?;
+        self.check_assignment(right_name, &right_indices, &left_value)?;
+
+        match left_indices {
+            Some(indices) => self.set_element(left_name, &indices, right_value)?,
+            None => self.set_variable(left_name, right_value)?,
+        }
+        match right_indices {
+            Some(indices) => self.set_element(right_name, &indices, left_value)?,
+            None => self.set_variable(right_name, left_value)?,
+        }
+
+        Ok(Value::None)
+    }
+
+    fn visit_clear_statement(&mut self) -> InterpreterResult {
+        // Unlike NEW the program and the position in it survive
+        let line = self.context.current_line;
+        self.reset()?;
+        self.context.current_line = line;
+
+        Ok(Value::None)
+    }
+
+    fn visit_vars_statement(&self) -> InterpreterResult {
+        if matches!(self.state, InterpreterState::Running) {
+            return Err(RuntimeError::InvalidState(String::from(
+                "VARS is only available in direct mode",
+            )));
+        }
+
+        let mut names: Vec<&String> = self.context.variables.keys().collect();
+        names.sort();
+
+        let mut lines: Vec<String> = vec![];
+        for name in names {
+            let line = match &self.context.variables[name] {
+                Value::Number(number) => format!("{} NUMBER {}", name, number),
+                Value::String(string) => format!("{} STRING \"{}\"", name, string),
+                Value::None => format!("{} NONE", name),
+            };
+            lines.push(line);
+        }
+
+        if lines.is_empty() {
+            return Ok(Value::None);
+        }
+
+        Ok(Value::String(lines.join("\n")))
+    }
+
+    fn next_data(&mut self) -> InterpreterResult {
+        let lines = &self.context.program.lines;
+        let data = &mut self.context.data;
+
+        while data.line < lines.len() {
+            if let Some(Line {
+                statement: Statement::DataStatement { values },
+                ..
+            }) = &lines[data.line]
+            {
+                if let Some(value) = values.get(data.item) {
+                    data.item += 1;
+                    return self.visit_expression(value);
+                }
+            }
+
+            data.line += 1;
+            data.item = 0;
+        }
+
+        Err(RuntimeError::OutOfData(self.context.current_line))
+    }
+
+    fn visit_read_statement(&mut self, variables: &Vec<Identifier>) -> InterpreterResult {
+        for variable in variables {
+            let value = self.next_data()?;
+            self.set_variable(&variable.name, value)?;
+        }
+
+        Ok(Value::None)
+    }
+
+    fn visit_restore_statement(&mut self, location: &Option<Expression>) -> InterpreterResult {
+        let line = match location {
+            Some(location) => self.visit_line_number(location)?,
+            None => 0,
+        };
+
+        self.context.data = DataPointer { line, item: 0 };
+
+        Ok(Value::None)
+    }
+
+    fn get_array(&self, name: &Identifier) -> Result<&Array, RuntimeError> {
+        self.context
+            .arrays
+            .get(&name.name)
+            .ok_or(RuntimeError::UndefinedVariable(
+                name.name.to_string(),
+                self.context.current_line,
+            ))
+    }
+
+    fn matrix_shape(&self, array: &Array) -> Result<(usize, usize), RuntimeError> {
+        array
+            .matrix_shape()
+            .ok_or(RuntimeError::DimensionMismatch(self.context.current_line))
+    }
+
+    fn get_matrix(&self, name: &Identifier) -> Result<Matrix, RuntimeError> {
+        let array = self.get_array(name)?;
+        let (rows, columns) = self.matrix_shape(array)?;
+
+        let mut matrix = Matrix::new(rows, columns, 0.0);
+        for row in 0..rows {
+            for column in 0..columns {
+                match array
+                    .matrix_offset(row, column)
+                    .map(|offset| &array.values[offset])
+                {
+                    Some(Value::Number(number)) => matrix.set(row, column, *number),
+                    _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+                }
+            }
+        }
+
+        Ok(matrix)
+    }
+
+    fn set_matrix(&mut self, name: &Identifier, matrix: &Matrix) -> Result<(), RuntimeError> {
+        let line = self.context.current_line;
+        if name.name.ends_with('$') {
+            return Err(RuntimeError::InvalidOperation(line));
+        }
+
+        let base = self.context.options.base;
+        let array = self
+            .context
+            .arrays
+            .entry(name.name.to_string())
+            .or_insert_with(|| {
+                Array::with_bounds(&[matrix.rows, matrix.columns], base, Value::Number(0.0))
+                    .unwrap()
+            });
+
+        if array.matrix_shape() != Some((matrix.rows, matrix.columns)) {
+            return Err(RuntimeError::DimensionMismatch(line));
+        }
+
+        for row in 0..matrix.rows {
+            for column in 0..matrix.columns {
+                if let Some(offset) = array.matrix_offset(row, column) {
+                    array.values[offset] = Value::Number(matrix.get(row, column));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Shape for ZER, CON and IDN, a given size redimensions the target
+    fn fill_shape(
+        &mut self,
+        target: &Identifier,
+        size: &Vec<Expression>,
+    ) -> Result<(usize, usize), RuntimeError> {
+        if !size.is_empty() {
+            let array = self.new_array(&target.name, size)?;
+            self.context.arrays.insert(target.name.to_string(), array);
+        }
+
+        self.matrix_shape(self.get_array(target)?)
+    }
+
+    fn visit_mat_statement(
+        &mut self,
+        target: &Identifier,
+        value: &MatrixExpression,
+    ) -> InterpreterResult {
+        let mismatch = RuntimeError::DimensionMismatch(self.context.current_line);
+
+        let matrix = match value {
+            MatrixExpression::Array(array) => self.get_matrix(array)?,
+            MatrixExpression::Add(left, right) => self
+                .get_matrix(left)?
+                .add(&self.get_matrix(right)?)
+                .ok_or(mismatch)?,
+            MatrixExpression::Subtract(left, right) => self
+                .get_matrix(left)?
+                .subtract(&self.get_matrix(right)?)
+                .ok_or(mismatch)?,
+            MatrixExpression::Multiply(left, right) => self
+                .get_matrix(left)?
+                .multiply(&self.get_matrix(right)?)
+                .ok_or(mismatch)?,
+            MatrixExpression::Transpose(array) => self.get_matrix(array)?.transpose(),
+            MatrixExpression::Inverse(array) => {
+                let matrix = self.get_matrix(array)?;
+                if matrix.rows != matrix.columns {
+                    return Err(mismatch);
+                }
+
+                matrix.inverse().ok_or(RuntimeError::IllegalFunctionCall(
+                    String::from("INV"),
+                    self.context.current_line,
+                ))?
+            }
+            MatrixExpression::Zero(size) => {
+                let (rows, columns) = self.fill_shape(target, size)?;
+                Matrix::new(rows, columns, 0.0)
+            }
+            MatrixExpression::Constant(size) => {
+                let (rows, columns) = self.fill_shape(target, size)?;
+                Matrix::new(rows, columns, 1.0)
+            }
+            MatrixExpression::Identity(size) => {
+                let (rows, columns) = self.fill_shape(target, size)?;
+                Matrix::identity(rows, columns).ok_or(mismatch)?
+            }
+        };
+
+        self.set_matrix(target, &matrix)?;
+
+        Ok(Value::None)
+    }
+
+    fn visit_mat_read_statement(&mut self, arrays: &Vec<Identifier>) -> InterpreterResult {
+        for name in arrays {
+            let (rows, columns) = self.matrix_shape(self.get_array(name)?)?;
+
+            for row in 0..rows {
+                for column in 0..columns {
+                    let value = self.next_data()?;
+
+                    let line = self.context.current_line;
+                    let array = self.context.arrays.get_mut(&name.name).unwrap();
+                    let offset = array.matrix_offset(row, column).unwrap();
+                    if std::mem::discriminant(&array.values[offset])
+                        != std::mem::discriminant(&value)
+                    {
+                        return Err(RuntimeError::InvalidOperation(line));
+                    }
+                    array.values[offset] = value;
+                }
+            }
+        }
+
+        Ok(Value::None)
+    }
+
+    fn visit_mat_print_statement(&self, arrays: &Vec<Identifier>) -> InterpreterResult {
+        let mut output: Vec<String> = vec![];
+        for name in arrays {
+            let array = self.get_array(name)?;
+            let (rows, columns) = self.matrix_shape(array)?;
+
+            if !output.is_empty() {
+                output.push(String::new());
+            }
+
+            for row in 0..rows {
+                let mut results: Vec<String> = vec![];
+                for column in 0..columns {
+                    if let Some(offset) = array.matrix_offset(row, column) {
+                        results.push(format!("{}", array.values[offset]));
+                    }
+                }
+                output.push(results.join(" "));
+            }
+        }
+
+        Ok(Value::String(output.join("\n")))
+    }
+
+    fn visit_var_statement(&mut self, declaration: &VarDeclaration) -> InterpreterResult {
+        let value = self.visit_expression(&declaration.value)?;
+
+        if declaration.indices.is_empty() {
+            self.set_variable(&declaration.name, value)?;
+        } else {
+            let mut indices: Vec<Value> = vec![];
+            for index in &declaration.indices {
+                indices.push(self.visit_expression(index)?);
+            }
+
+            self.set_element(&declaration.name, &indices, value)?;
+        }
+
+        Ok(Value::None)
+    }
+
+    async fn visit_input_statement(&mut self, variables: &Vec<Identifier>) -> InterpreterResult {
+        for variable in variables {
+            set_prompt(format!("? ").as_str());
+
+            let input = read_line().await;
+            write_line(format!("? {}", input).as_str());
+
+            let mut parser = Parser::with_dialect(input.as_str(), self.dialect);
+            let expression = parser.parse_expression();
+
+            match expression {
+                Ok(expression) => {
+                    let value = self.visit_expression(&expression)?;
+                    self.set_variable(&variable.name, value)?;
+                }
+                Err(error) => return Err(RuntimeError::SyntaxError(error)),
+            };
+        }
+
+        Ok(Value::None)
+    }
+
+    fn visit_channel(&self, channel: &Expression) -> Result<usize, RuntimeError> {
+        match self.visit_expression(channel)? {
+            Value::Number(number) if number >= 1.0 => Ok(number as usize),
+            _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        }
+    }
+
+    fn visit_open_statement(
+        &mut self,
+        path: &Expression,
+        mode: FileMode,
+        channel: &Expression,
+        length: &Option<Expression>,
+    ) -> InterpreterResult {
+        let path = match self.visit_expression(path)? {
+            Value::String(path) => path,
+            _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        };
+        let channel = self.visit_channel(channel)?;
+        let length = match length {
+            Some(length) => match self.visit_expression(length)? {
+                Value::Number(number) if number >= 1.0 => number as usize,
+                _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+            },
+            None => DEFAULT_RECORD_LENGTH,
+        };
+
+        self.context.files.open(channel, path, mode, length)?;
+
+        Ok(Value::None)
+    }
+
+    fn random_file(&mut self, channel: usize) -> Result<&mut FileChannel, RuntimeError> {
+        let file = self.context.files.get_mut(channel)?;
+        if file.mode != FileMode::Random {
+            return Err(RuntimeError::BadFileMode(channel));
+        }
+
+        Ok(file)
+    }
+
+    fn visit_record_number(
+        &self,
+        record: &Option<Expression>,
+    ) -> Result<Option<usize>, RuntimeError> {
+        match record {
+            Some(record) => match self.visit_expression(record)? {
+                Value::Number(number) if number >= 0.0 => Ok(Some(number as usize)),
+                _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn visit_field_statement(
+        &mut self,
+        channel: &Expression,
+        fields: &Vec<FieldDeclaration>,
+    ) -> InterpreterResult {
+        let channel = self.visit_channel(channel)?;
+
+        let mut declarations: Vec<Field> = vec![];
+        for field in fields {
+            let width = match self.visit_expression(&field.width)? {
+                Value::Number(number) if number >= 1.0 => number as usize,
+                _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+            };
+
+            declarations.push(Field {
+                width,
+                variable: field.variable.name.to_string(),
+            });
+        }
+
+        self.random_file(channel)?
+            .set_fields(channel, declarations)?;
+
+        Ok(Value::None)
+    }
+
+    fn visit_get_statement(
+        &mut self,
+        channel: &Expression,
+        record: &Option<Expression>,
+    ) -> InterpreterResult {
+        let channel = self.visit_channel(channel)?;
+        let record = self.visit_record_number(record)?;
+
+        let file = self.random_file(channel)?;
+        let values = file.read_record(record)?;
+        let names: Vec<String> = file
+            .fields()
+            .iter()
+            .map(|field| field.variable.to_string())
+            .collect();
+
+        for (name, value) in names.iter().zip(values) {
+            self.set_variable(name, Value::String(value))?;
+        }
+
+        Ok(Value::None)
+    }
+
+    fn visit_put_statement(
+        &mut self,
+        channel: &Expression,
+        record: &Option<Expression>,
+    ) -> InterpreterResult {
+        let channel = self.visit_channel(channel)?;
+        let record = self.visit_record_number(record)?;
+
+        // The record is built from the current values of the FIELD variables
+        let variables = &self.context.variables;
+        let values: Vec<String> = self
+            .context
+            .files
+            .get(channel)?
+            .fields()
+            .iter()
+            .map(|field| match variables.get(&field.variable) {
+                Some(value) => format!("{}", value),
+                None => String::new(),
+            })
+            .collect();
+
+        self.random_file(channel)?.write_record(record, values)?;
+
+        Ok(Value::None)
+    }
+
+    fn visit_print_file_statement(
+        &mut self,
+        channel: &Expression,
+        expressions: &Vec<Expression>,
+    ) -> InterpreterResult {
+        let channel = self.visit_channel(channel)?;
+
+        // Fields are written comma separated so that INPUT # can read them back
+        let mut fields: Vec<String> = vec![];
+        for expression in expressions {
+            fields.push(match self.visit_expression(expression)? {
+                Value::String(string) if string.contains([',', '"', '\n']) => {
+                    format!("\"{}\"", string.replace('"', "\"\""))
+                }
+                value => format!("{}", value),
+            });
+        }
+
+        let file = self.context.files.get_mut(channel)?;
+        if !matches!(file.mode, FileMode::Output | FileMode::Append) {
+            return Err(RuntimeError::BadFileMode(channel));
         }
+        file.write(format!("{}\n", fields.join(",")).as_str());
+
+        Ok(Value::None)
     }
 
-    async fn visit_run_statement(&mut self) -> InterpreterResult {
-        self.state = InterpreterState::Running;
+    fn visit_input_file_statement(
+        &mut self,
+        channel: &Expression,
+        variables: &Vec<Identifier>,
+    ) -> InterpreterResult {
+        let channel = self.visit_channel(channel)?;
 
-        self.reset();
+        for variable in variables {
+            let file = self.context.files.get_mut(channel)?;
+            if file.mode != FileMode::Input {
+                return Err(RuntimeError::BadFileMode(channel));
+            }
 
-        while self.context.current_line < self.context.program.lines.len() {
-            let line = self.context.program.get(self.context.current_line);
-            self.context.current_line += 1;
-            match line {
-                Some(line) => {
-                    let value = self.visit_statement(&line.statement).await?;
+            let field = match file.read_field() {
+                Some(field) => field,
+                None => return Err(RuntimeError::InputPastEnd(channel)),
+            };
 
-                    if value != Value::None {
-                        write_line(format!("{}", value).as_str());
+            let value = if variable.name.ends_with('$') {
+                Value::String(field)
+            } else {
+                match field.parse() {
+                    Ok(number) => Value::Number(number),
+                    Err(_) => {
+                        return Err(RuntimeError::InvalidOperation(self.context.current_line))
                     }
                 }
-                None => {}
             };
-        }
 
-        self.state = InterpreterState::Stopped;
+            self.set_variable(&variable.name, value)?;
+        }
 
         Ok(Value::None)
     }
 
-    fn visit_var_statement(&mut self, declaration: &VarDeclaration) -> InterpreterResult {
-        let value = self.visit_expression(&declaration.value)?;
-        self.context
-            .variables
-            .insert(declaration.name.to_string(), value);
+    fn visit_line_input_file_statement(
+        &mut self,
+        channel: &Expression,
+        variable: &Identifier,
+    ) -> InterpreterResult {
+        let channel = self.visit_channel(channel)?;
+
+        let file = self.context.files.get_mut(channel)?;
+        if file.mode != FileMode::Input {
+            return Err(RuntimeError::BadFileMode(channel));
+        }
+
+        let line = match file.read_line() {
+            Some(line) => line,
+            None => return Err(RuntimeError::InputPastEnd(channel)),
+        };
+
+        self.set_variable(&variable.name, Value::String(line))?;
 
         Ok(Value::None)
     }
 
-    async fn visit_input_statement(&mut self, variables: &Vec<Identifier>) -> InterpreterResult {
-        for variable in variables {
-            set_prompt(format!("? ").as_str());
+    fn visit_close_statement(&mut self, channels: &Vec<Expression>) -> InterpreterResult {
+        if channels.is_empty() {
+            self.context.files.close_all()?;
+        }
 
-            let input = read_line().await;
-            write_line(format!("? {}", input).as_str());
+        for channel in channels {
+            let channel = self.visit_channel(channel)?;
+            self.context.files.close(channel)?;
+        }
 
-            let mut parser = Parser::new(input.as_str());
-            let expression = parser.parse_expression();
+        Ok(Value::None)
+    }
 
-            match expression {
-                Ok(expression) => {
-                    let value = self.visit_expression(&expression)?;
-                    self.context
-                        .variables
-                        .insert(variable.name.to_string(), value);
-                }
-                Err(error) => return Err(RuntimeError::SyntaxError(error)),
-            };
+    fn visit_poke_statement(
+        &mut self,
+        address: &Expression,
+        value: &Expression,
+    ) -> InterpreterResult {
+        let address = match self.visit_expression(address)? {
+            Value::Number(address) => self.to_address(address, "POKE")?,
+            _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        };
+        let value = match self.visit_expression(value)? {
+            Value::Number(value) if (0.0..256.0).contains(&value) => value as u8,
+            _ => {
+                return Err(RuntimeError::IllegalFunctionCall(
+                    String::from("POKE"),
+                    self.context.current_line,
+                ))
+            }
+        };
+
+        // The system area is read only
+        if self.system_word(address).is_none() {
+            self.context.memory.poke(address, value);
         }
 
         Ok(Value::None)
     }
 
+    // Included lines belong to their library, LIST and SAVE show the program alone
+    fn listing(&self) -> String {
+        self.context
+            .program
+            .print(|line| !self.context.included.contains_key(&line.number.unwrap()))
+    }
+
     fn visit_list_statement(&self) -> InterpreterResult {
-        Ok(Value::String(self.context.program.print()))
+        Ok(Value::String(self.listing()))
     }
 
     fn visit_cls_statement(&self) -> InterpreterResult {
@@ -285,36 +2254,217 @@ impl Interpreter {
         Ok(Value::None)
     }
 
-    fn visit_return_statement(&mut self) -> InterpreterResult {
+    // RETURN <line> drops the return address and continues at line instead
+    fn visit_return_statement(&mut self, location: &Option<Expression>) -> InterpreterResult {
+        let target = match location {
+            Some(location) => Some(self.visit_line_number(location)?),
+            None => None,
+        };
+
         match self.context.stack.pop() {
             Some(location) => {
-                self.context.current_line = location;
+                self.context.current_line = target.unwrap_or(location);
+                if self.context.events.handler_depth == Some(self.context.stack.len()) {
+                    self.context.events.handler_depth = None;
+                }
+                Ok(Value::None)
+            }
+            // A spawned task ends when it returns from its entry line
+            None if self.context.tasks.current != 0 => {
+                self.context.current_line = self.context.program.lines.len();
                 Ok(Value::None)
             }
             None => Ok(Value::None),
         }
     }
 
-    fn visit_end_statement(&mut self) -> InterpreterResult {
+    fn visit_spawn_statement(&mut self, location: &Expression) -> InterpreterResult {
+        let line = self.visit_line_number(location)?;
+        self.context.tasks.next_id += 1;
+        let task = Task {
+            id: self.context.tasks.next_id,
+            parent: Some(self.context.tasks.current),
+            current_line: line,
+            stack: Vec::new(),
+            handler_depth: None,
+        };
+        self.context.tasks.suspended.push_back(task);
+        Ok(Value::None)
+    }
+
+    fn visit_yield_statement(&mut self) -> InterpreterResult {
+        self.switch_task(true);
+        Ok(Value::None)
+    }
+
+    fn visit_wait_statement(&mut self) -> InterpreterResult {
+        let current = self.context.tasks.current;
+        let waiting = self
+            .context
+            .tasks
+            .suspended
+            .iter()
+            .any(|task| task.parent == Some(current));
+
+        // Run WAIT again when this task resumes, until its children have ended
+        if waiting {
+            self.context.current_line -= 1;
+            self.switch_task(true);
+        }
+        Ok(Value::None)
+    }
+
+    fn visit_assert_statement(
+        &mut self,
+        condition: &Expression,
+        message: &Option<Expression>,
+    ) -> InterpreterResult {
+        let operand = |value: &Value| match value {
+            Value::String(string) => format!("\"{}\"", string),
+            value => format!("{}", value),
+        };
+
+        // A comparison reports both of the sides it compared
+        let (passed, values) = match condition {
+            Expression::RelationExpression(relation) => {
+                let left = self.visit_expression(&relation.left)?;
+                let right = self.visit_expression(&relation.right)?;
+                let operator = match relation.operator {
+                    RelationOperator::Equal => "=",
+                    RelationOperator::NotEqual => "<>",
+                    RelationOperator::LessThan => "<",
+                    RelationOperator::LessThanOrEqual => "<=",
+                    RelationOperator::GreaterThan => ">",
+                    RelationOperator::GreaterThanOrEqual => ">=",
+                };
+                let values = format!("{} {} {}", operand(&left), operator, operand(&right));
+
+                (self.compare(&relation.operator, left, right)?, values)
+            }
+            _ => match self.visit_expression(condition)? {
+                Value::Number(number) => (number != 0.0, operand(&Value::Number(number))),
+                _ => return Err(RuntimeError::InvalidOperation(self.context.current_line)),
+            },
+        };
+        if passed {
+            return Ok(Value::None);
+        }
+
+        let detail = match message {
+            Some(message) => format!("{} ({})", self.visit_expression(message)?, values),
+            None => values,
+        };
+        // Direct mode has no line to report
+        let line =
+            matches!(self.state, InterpreterState::Running).then(|| self.context.current_line - 1);
+        Err(RuntimeError::AssertionFailed(detail, line))
+    }
+
+    fn visit_exit_code(&self, code: &Option<Expression>) -> Result<i32, RuntimeError> {
+        match code {
+            None => Ok(0),
+            Some(code) => match self.visit_expression(code)? {
+                Value::Number(number) => Ok(number as i32),
+                _ => Err(RuntimeError::TypeMismatch(self.context.current_line)),
+            },
+        }
+    }
+
+    fn visit_end_statement(&mut self, code: &Option<Expression>) -> InterpreterResult {
+        self.context.exit_code = self.visit_exit_code(code)?;
         self.context.current_line = self.context.program.lines.len();
         Ok(Value::None)
     }
 
+    fn visit_system_statement(&mut self, code: &Option<Expression>) -> InterpreterResult {
+        let code = self.visit_exit_code(code)?;
+        self.stop()?;
+        exit(code);
+        Ok(Value::None)
+    }
+
     fn visit_help_statement(&self) -> InterpreterResult {
         Ok(Value::String(
             vec![
                 "PRINT <expression>[, <expression>...]",
                 "INPUT <variable>[, <variable>...]",
+                "OPEN <file> FOR INPUT | OUTPUT | APPEND AS #<n>",
+                "PRINT #<n>, <expression>[, <expression>...]",
+                "INPUT #<n>, <variable>[, <variable>...]",
+                "LINE INPUT #<n>, <variable>",
+                "CLOSE [#<n>[, #<n>...]]",
+                "EOF(<n>)",
+                "OPEN <file> [FOR RANDOM] AS #<n> [LEN=<length>]",
+                "FIELD #<n>, <width> AS <variable>[, <width> AS <variable>...]",
+                "GET #<n>[, <record>]",
+                "PUT #<n>[, <record>]",
+                "MKI$(<n>), MKS$(<n>), CVI(<s>), CVS(<s>)",
+                "POKE <address>, <value>",
+                "PEEK(<address>), USR(<address>[, <argument>...])",
+                "DIALECT STANDARD | PALOALTO",
+                "OPTION COMPARE BINARY | TEXT",
                 "IF <condition> THEN <statement>",
-                "LET <variable> = <expression>",
+                "[LET] <variable> = <expression>",
                 "GOTO <line>",
                 "GOSUB <line>",
+                "SELECT CASE <expression>",
+                "CASE <value>[, <value>...] | CASE <from> TO <to> | CASE IS <operator> <value>",
+                "CASE ELSE",
+                "END SELECT",
+                "ON ERROR GOTO <line>",
+                "RESUME [NEXT | <line>]",
+                "ON TIMER(<seconds>) GOSUB <line>",
+                "ON KEY GOSUB <line>",
+                "TIMER ON | OFF",
+                "KEY ON | OFF",
                 "REM <comment>",
-                "RETURN",
-                "END",
+                "' <comment>",
+                "RETURN [<line>]",
+                "ASSERT <condition>[, <message>]",
+                "TEST",
+                "SPAWN <line>",
+                "YIELD",
+                "WAIT",
+                "END [<status>]",
+                "SYSTEM [<status>]",
+                "STOP",
+                "CONT",
+                "TRON [VARS]",
+                "TROFF",
+                "RANDOMIZE [<seed> | TIMER]",
+                "RND(<x>), INT(<x>), ABS(<x>)",
+                "SLEEP <seconds>",
+                "PAUSE <milliseconds>",
+                "TIMER, DATE$, TIME$",
+                "INKEY$",
+                "COMMAND$, ARGC, ARGV$(<i>), ENVIRON$(<name>)",
+                "LEN(<s>), LEFT$(<s>, <n>), RIGHT$(<s>, <n>), MID$(<s>, <i>[, <n>])",
+                "INSTR([<i>, ]<s>, <t>), ASC(<s>), CHR$(<n>), STR$(<x>), VAL(<s>)",
+                "<x> AND | OR | XOR <y>, NOT <x>",
+                "SHL(<x>, <n>), SHR(<x>, <n>), HEX$(<x>), BIN$(<x>)",
                 "CLS",
                 "LIST",
                 "RUN",
+                "CHAIN <file>",
+                "INCLUDE <file>",
+                "COMMON <variable>[, <variable>...]",
+                "DIM <array>(<size>[, <size>...])[, ...]",
+                "ERASE <array>[, <array>...]",
+                "SWAP <variable>, <variable>",
+                "CLEAR",
+                "CONST <name> = <expression>",
+                "DIM <variable>[, ...]",
+                "OPTION BASE 0 | 1",
+                "OPTION EXPLICIT | STRICT",
+                "VARS",
+                "DATA <value>[, <value>...]",
+                "READ <variable>[, <variable>...]",
+                "RESTORE [<line>]",
+                "MAT READ | MAT PRINT <array>[, <array>...]",
+                "MAT <array> = <array> [+ | - | * <array>]",
+                "MAT <array> = TRN(<array>) | INV(<array>)",
+                "MAT <array> = ZER | CON | IDN [(<size>[, <size>])]",
+                "MERGE <file>",
                 "NEW",
             ]
             .join("\n"),
@@ -322,7 +2472,7 @@ impl Interpreter {
     }
 
     fn visit_new_statement(&mut self) -> InterpreterResult {
-        self.new_program();
+        self.new_program()?;
         Ok(Value::None)
     }
 
@@ -337,7 +2487,7 @@ impl Interpreter {
     }
 
     async fn visit_save_statement(&mut self) -> InterpreterResult {
-        save_file(self.context.program.print().as_str());
+        save_file(self.listing().as_str());
 
         Ok(Value::None)
     }
@@ -356,22 +2506,75 @@ impl Interpreter {
             Statement::InputStatement { variables } => {
                 return self.visit_input_statement(variables).await;
             }
+            Statement::OpenStatement {
+                path,
+                mode,
+                channel,
+                length,
+            } => self.visit_open_statement(path, *mode, channel, length),
+            Statement::FieldStatement { channel, fields } => {
+                self.visit_field_statement(channel, fields)
+            }
+            Statement::GetStatement { channel, record } => {
+                self.visit_get_statement(channel, record)
+            }
+            Statement::PutStatement { channel, record } => {
+                self.visit_put_statement(channel, record)
+            }
+            Statement::PrintFileStatement {
+                channel,
+                expressions,
+            } => self.visit_print_file_statement(channel, expressions),
+            Statement::InputFileStatement { channel, variables } => {
+                self.visit_input_file_statement(channel, variables)
+            }
+            Statement::LineInputFileStatement { channel, variable } => {
+                self.visit_line_input_file_statement(channel, variable)
+            }
+            Statement::CloseStatement { channels } => self.visit_close_statement(channels),
             Statement::GoToStatement { location } => {
                 return self.visit_goto_statement(location);
             }
             Statement::GoSubStatement { location } => {
                 return self.visit_gosub_statement(location);
             }
-            Statement::EndStatement => return self.visit_end_statement(),
+            Statement::SelectStatement { discriminant } => {
+                self.visit_select_statement(discriminant)
+            }
+            Statement::CaseStatement { .. } | Statement::CaseElseStatement => {
+                self.visit_case_statement()
+            }
+            Statement::EndSelectStatement => Ok(Value::None),
+            Statement::OnErrorStatement { location } => self.visit_on_error_statement(location),
+            Statement::ResumeStatement { target } => self.visit_resume_statement(target),
+            Statement::TronStatement { variables } => self.visit_tron_statement(*variables),
+            Statement::TroffStatement => self.visit_troff_statement(),
+            Statement::RandomizeStatement { seed } => self.visit_randomize_statement(seed),
+            Statement::SleepStatement { duration } => {
+                return self.sleep(duration, 1000.0).await;
+            }
+            Statement::PauseStatement { duration } => {
+                return self.sleep(duration, 1.0).await;
+            }
+            Statement::OnTimerStatement { interval, location } => {
+                self.visit_on_timer_statement(interval, location)
+            }
+            Statement::OnKeyStatement { location } => self.visit_on_key_statement(location),
+            Statement::TimerStatement { enabled } => self.visit_timer_statement(*enabled),
+            Statement::KeyStatement { enabled } => self.visit_key_statement(*enabled),
+            Statement::StopStatement => self.visit_stop_statement(),
+            Statement::ContStatement => {
+                return Box::pin(self.visit_cont_statement()).await;
+            }
+            Statement::EndStatement { code } => self.visit_end_statement(code),
+            Statement::SystemStatement { code } => self.visit_system_statement(code),
             Statement::ListStatement => {
                 return self.visit_list_statement();
             }
             Statement::RunStatement => {
                 return Box::pin(self.visit_run_statement()).await;
             }
-            Statement::ReturnStatement => {
-                return self.visit_return_statement();
-            }
+            Statement::ReturnStatement { location } => self.visit_return_statement(location),
             Statement::Empty => {
                 return Ok(Value::None);
             }
@@ -384,9 +2587,44 @@ impl Interpreter {
             Statement::NewStatement => {
                 return self.visit_new_statement();
             }
-            Statement::RemStatement => {
+            Statement::RemStatement { .. } => {
                 return Ok(Value::None);
             }
+            Statement::OptionStatement { directive } => self.visit_option_statement(directive),
+            Statement::DialectStatement { dialect } => {
+                self.dialect = *dialect;
+                Ok(Value::None)
+            }
+            Statement::PokeStatement { address, value } => {
+                self.visit_poke_statement(address, value)
+            }
+            Statement::ChainStatement { path } => {
+                return Box::pin(self.visit_chain_statement(path)).await;
+            }
+            Statement::MergeStatement { path } => self.visit_merge_statement(path),
+            Statement::DimStatement { declarations } => self.visit_dim_statement(declarations),
+            Statement::EraseStatement { arrays } => self.visit_erase_statement(arrays),
+            Statement::SwapStatement { left, right } => self.visit_swap_statement(left, right),
+            Statement::ConstStatement { name, value } => self.visit_const_statement(name, value),
+            Statement::ClearStatement => self.visit_clear_statement(),
+            Statement::VarsStatement => self.visit_vars_statement(),
+            Statement::DataStatement { .. } => Ok(Value::None),
+            Statement::ReadStatement { variables } => self.visit_read_statement(variables),
+            Statement::RestoreStatement { location } => self.visit_restore_statement(location),
+            Statement::MatStatement { target, value } => self.visit_mat_statement(target, value),
+            Statement::MatReadStatement { arrays } => self.visit_mat_read_statement(arrays),
+            Statement::MatPrintStatement { arrays } => self.visit_mat_print_statement(arrays),
+            Statement::IncludeStatement { path } => self.visit_include_statement(path),
+            Statement::SpawnStatement { location } => self.visit_spawn_statement(location),
+            Statement::YieldStatement => self.visit_yield_statement(),
+            Statement::WaitStatement => self.visit_wait_statement(),
+            Statement::AssertStatement { condition, message } => {
+                self.visit_assert_statement(condition, message)
+            }
+            Statement::TestStatement => {
+                return Box::pin(self.visit_test_statement()).await;
+            }
+            Statement::CommonStatement { .. } => Ok(Value::None),
             Statement::LoadStatement => {
                 return self.visit_load_statement().await;
             }
@@ -398,7 +2636,9 @@ impl Interpreter {
 
     async fn eval(&mut self, ast: Line) -> InterpreterResult {
         if ast.number.is_some() {
-            self.context.program.set(ast);
+            // Editing the program invalidates a paused run
+            self.state = InterpreterState::Stopped;
+            self.set_lines(vec![ast])?;
         } else {
             return self.visit_statement(&ast.statement).await;
         }
@@ -415,7 +2655,7 @@ impl Interpreter {
             let source = read_line().await;
             write_line(format!(":{}", source).as_str());
 
-            let mut parser = Parser::new(source.as_str());
+            let mut parser = Parser::with_dialect(source.as_str(), self.dialect);
             let ast = parser.parse();
 
             if ast.is_err() {
@@ -439,23 +2679,568 @@ impl Interpreter {
         }
     }
 
+    // Runs a script given on the command line and returns its exit status
+    pub async fn run_script(&mut self, source: String) -> i32 {
+        let result = match self.load_source(source) {
+            Ok(()) => self.visit_run_statement().await,
+            Err(error) => Err(error),
+        };
+
+        match result {
+            // A script cannot be continued after STOP, so it fails like an error would
+            Ok(_) if matches!(self.state, InterpreterState::Paused) => match self.stop() {
+                Ok(()) => 1,
+                Err(error) => self.exit_with_error(error),
+            },
+            Ok(_) => self.context.exit_code,
+            Err(error) => {
+                self.stop().ok();
+                self.exit_with_error(error)
+            }
+        }
+    }
+
+    // Uncaught errors exit with their ERR code
+    fn exit_with_error(&self, error: RuntimeError) -> i32 {
+        write_line(format!("{}", error).as_str());
+        error.code() as i32
+    }
+
     pub fn load_program(&mut self, source: String) {
-        self.new_program();
+        if let Err(error) = self.load_source(source) {
+            write_line(format!("{}", error).as_str());
+        }
+
+        write_line("program loaded");
+    }
+}
+
+impl Interpreter {
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.context.clock = clock;
+    }
+
+    pub fn register_routine(&mut self, address: u16, routine: Routine) {
+        self.context.memory.register(address, routine);
+    }
+
+    // Replaces the program with the parsed source, stopping any run
+    fn load_source(&mut self, source: String) -> Result<(), RuntimeError> {
+        let mut parser = Parser::with_dialect(source.as_str(), self.dialect);
+        let lines = parser.parse_program().map_err(RuntimeError::SyntaxError)?;
+
+        self.state = InterpreterState::Stopped;
+        self.context.files.close_all()?;
+        self.replace_program(lines)
+    }
+
+    fn check_line_numbers(lines: &[Line]) -> Result<(), RuntimeError> {
+        match lines
+            .iter()
+            .filter_map(|line| line.number)
+            .find(|number| *number >= MAX_LINES)
+        {
+            Some(number) => Err(RuntimeError::IllegalLineNumber(number.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    // Returns the numbers of the lines that were already in the program
+    fn set_lines(&mut self, lines: Vec<Line>) -> Result<Vec<usize>, RuntimeError> {
+        Self::check_line_numbers(&lines)?;
+
+        let mut conflicts: Vec<usize> = vec![];
+        for line in lines {
+            let number = match line.number {
+                Some(number) => number,
+                None => continue,
+            };
+
+            if self.context.program.get(number).is_some() {
+                conflicts.push(number);
+            }
+            self.context.included.remove(&number);
+            self.context.program.set(line);
+        }
 
-        let mut parser = Parser::new(source.as_str());
-        let ast = parser.parse();
+        Ok(conflicts)
+    }
+
+    fn visit_path(&self, path: &Expression) -> Result<String, RuntimeError> {
+        match self.visit_expression(path)? {
+            Value::String(name) => Ok(name),
+            _ => Err(RuntimeError::InvalidOperation(self.context.current_line)),
+        }
+    }
+
+    fn read_source(&self, name: &str) -> Result<String, RuntimeError> {
+        match read_file(name).or_else(|| read_file(&format!("{}.bas", name))) {
+            Some(source) => Ok(source),
+            None => Err(RuntimeError::FileNotFound(name.to_string())),
+        }
+    }
 
-        match ast {
-            Ok(ast) => {
-                for line in ast {
-                    self.context.program.set(line);
+    fn read_program(&self, path: &Expression) -> Result<Vec<Line>, RuntimeError> {
+        let source = self.read_source(&self.visit_path(path)?)?;
+
+        let mut parser = Parser::with_dialect(source.as_str(), self.dialect);
+        parser.parse().map_err(RuntimeError::SyntaxError)
+    }
+
+    async fn visit_chain_statement(&mut self, path: &Expression) -> InterpreterResult {
+        let lines = self.read_program(path)?;
+
+        // Only the variables listed in a COMMON statement survive the chain
+        let mut common: HashMap<String, Value> = HashMap::new();
+        for line in self.context.program.iter() {
+            if let Statement::CommonStatement { variables } = line.statement {
+                for variable in variables {
+                    if let Some(value) = self.context.variables.get(&variable.name) {
+                        common.insert(variable.name, value.clone());
+                    }
                 }
             }
-            Err(error) => {
-                write_line(format!("{}", error).as_str());
+        }
+
+        // Open files stay open for the chained program
+        self.replace_program(lines)?;
+        self.prepare_run()?;
+        self.context.variables = common;
+
+        if matches!(self.state, InterpreterState::Running) {
+            return Ok(Value::None);
+        }
+
+        self.run().await
+    }
+
+    fn visit_merge_statement(&mut self, path: &Expression) -> InterpreterResult {
+        let lines = self.read_program(path)?;
+
+        if !matches!(self.state, InterpreterState::Running) {
+            self.state = InterpreterState::Stopped;
+        }
+
+        let conflicts = self.set_lines(lines)?;
+        if conflicts.is_empty() {
+            return Ok(Value::None);
+        }
+
+        let numbers: Vec<String> = conflicts.iter().map(|number| number.to_string()).collect();
+
+        Ok(Value::String(format!(
+            "Replaced existing lines: {}",
+            numbers.join(", ")
+        )))
+    }
+
+    // Collects the numbered lines of a file and of everything it includes in turn
+    fn read_include(
+        &self,
+        name: String,
+        chain: &mut Vec<String>,
+        lines: &mut Vec<(String, Line)>,
+    ) -> Result<(), RuntimeError> {
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(RuntimeError::CircularInclude(chain.join(" -> ")));
+        }
+
+        let source = self.read_source(&name)?;
+        chain.push(name.clone());
+
+        // Parsed one line at a time so that errors can name the line
+        for (index, text) in source.lines().enumerate() {
+            let mut parser = Parser::with_dialect(text, self.dialect);
+            let parsed = parser.parse().map_err(|error| {
+                RuntimeError::IncludeSyntaxError(name.clone(), index + 1, error)
+            })?;
+
+            for line in parsed {
+                if line.number.is_none() {
+                    continue;
+                }
+
+                if let Statement::IncludeStatement { path } = &line.statement {
+                    let nested = resolve_include(&name, &self.visit_path(path)?);
+                    self.read_include(nested, chain, lines)?;
+                }
+                lines.push((name.clone(), line));
             }
         }
 
-        write_line("program loaded");
+        chain.pop();
+
+        Ok(())
+    }
+
+    // Lines keep their own numbers, a library owns a range that the program must leave free.
+    // Including the same file again only finds identical lines, so it is not a conflict.
+    fn visit_include_statement(&mut self, path: &Expression) -> InterpreterResult {
+        let mut lines: Vec<(String, Line)> = vec![];
+        self.read_include(self.visit_path(path)?, &mut vec![], &mut lines)?;
+
+        let mut sources: HashMap<usize, &str> = HashMap::new();
+        for (file, line) in &lines {
+            let number = line.number.unwrap();
+            if number >= self.context.program.lines.len() {
+                return Err(RuntimeError::IllegalLineNumber(format!(
+                    "{} in {}",
+                    number, file
+                )));
+            }
+
+            let existing = match self.context.program.lines[number].as_ref() {
+                Some(existing) => Some(existing.source.trim()),
+                None => sources.get(&number).copied(),
+            };
+
+            if existing.is_some_and(|source| source != line.source.trim()) {
+                return Err(RuntimeError::LineConflict(file.to_string(), number));
+            }
+            sources.insert(number, line.source.trim());
+        }
+
+        for (file, line) in lines {
+            self.context.included.insert(line.number.unwrap(), file);
+            self.context.program.set(line);
+        }
+
+        Ok(Value::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{DateTime, FixedClock};
+
+    async fn run(interpreter: &mut Interpreter, source: &str) -> InterpreterResult {
+        let lines = Parser::with_dialect(source, Dialect::Standard)
+            .parse()
+            .unwrap();
+        interpreter.set_lines(lines).unwrap();
+        interpreter.visit_run_statement().await
+    }
+
+    fn variable(interpreter: &Interpreter, name: &str) -> Value {
+        interpreter.context.variables[name].clone()
+    }
+
+    fn fixed_clock() -> Box<FixedClock> {
+        Box::new(FixedClock {
+            time: DateTime {
+                year: 2021,
+                month: 3,
+                day: 4,
+                hour: 1,
+                minute: 2,
+                second: 3,
+                millisecond: 500,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn fixed_clock_sets_time_functions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_clock(fixed_clock());
+        run(
+            &mut interpreter,
+            "10 LET T = TIMER\n20 LET D$ = DATE$\n30 LET H$ = TIME$",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(variable(&interpreter, "T"), Value::Number(3723.5));
+        assert_eq!(
+            variable(&interpreter, "D$"),
+            Value::String(String::from("03-04-2021"))
+        );
+        assert_eq!(
+            variable(&interpreter, "H$"),
+            Value::String(String::from("01:02:03"))
+        );
+    }
+
+    #[tokio::test]
+    async fn fixed_clock_skips_sleep() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_clock(fixed_clock());
+
+        let start = std::time::Instant::now();
+        run(
+            &mut interpreter,
+            "10 SLEEP 60\n20 PAUSE 60000\n30 LET A = 1",
+        )
+        .await
+        .unwrap();
+
+        assert!(start.elapsed().as_secs() < 1);
+        assert_eq!(variable(&interpreter, "A"), Value::Number(1.0));
+    }
+
+    async fn run_with_keys(source: &str, keys: &str) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        let lines = Parser::with_dialect(source, Dialect::Standard)
+            .parse()
+            .unwrap();
+        interpreter.set_lines(lines).unwrap();
+        interpreter.reset().unwrap();
+        interpreter
+            .context
+            .events
+            .keys
+            .get_mut()
+            .extend(keys.chars());
+        interpreter.run().await.unwrap();
+        interpreter
+    }
+
+    #[tokio::test]
+    async fn on_key_runs_once_per_key() {
+        let source = "5 LET C = 0\n6 LET N = 0\n10 ON KEY GOSUB 100\n20 KEY ON\n\
+                      30 LET C = C + 1\n40 IF C < 20 THEN GOTO 30\n50 END\n\
+                      100 LET N = N + 1\n110 RETURN";
+        let interpreter = run_with_keys(source, "abc").await;
+
+        assert_eq!(variable(&interpreter, "N"), Value::Number(3.0));
+    }
+
+    #[tokio::test]
+    async fn handler_left_by_return_line_rearms_events() {
+        let source = "5 LET C = 0\n6 LET N = 0\n10 ON KEY GOSUB 100\n20 KEY ON\n\
+                      30 LET C = C + 1\n40 IF C < 20 THEN GOTO 30\n50 END\n\
+                      100 LET N = N + 1\n110 RETURN 30";
+        let interpreter = run_with_keys(source, "ab").await;
+
+        assert_eq!(variable(&interpreter, "N"), Value::Number(2.0));
+        assert!(interpreter.context.stack.is_empty());
+    }
+
+    async fn evaluate(expression: &str) -> Value {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, &format!("10 LET R = {}", expression))
+            .await
+            .unwrap();
+        variable(&interpreter, "R")
+    }
+
+    #[tokio::test]
+    async fn bitwise_operators_work_on_integers() {
+        assert_eq!(evaluate("12 AND 10").await, Value::Number(8.0));
+        assert_eq!(evaluate("12 OR 3").await, Value::Number(15.0));
+        assert_eq!(evaluate("5 XOR 1").await, Value::Number(4.0));
+        assert_eq!(evaluate("NOT 0").await, Value::Number(-1.0));
+        assert_eq!(evaluate("-1 AND 65535").await, Value::Number(-1.0));
+        assert_eq!(evaluate("(1 < 2) AND (3 > 4)").await, Value::Number(0.0));
+        assert_eq!(evaluate("NOT (1 = 1) OR 8").await, Value::Number(8.0));
+    }
+
+    #[tokio::test]
+    async fn operators_group_by_precedence_then_left_to_right() {
+        assert_eq!(evaluate("10 - 2 - 3").await, Value::Number(5.0));
+        assert_eq!(evaluate("24 / 4 / 2").await, Value::Number(3.0));
+        assert_eq!(evaluate("2 * 3 + 4").await, Value::Number(10.0));
+        assert_eq!(evaluate("2 + 3 * 4").await, Value::Number(14.0));
+        assert_eq!(evaluate("10 - 2 + 3").await, Value::Number(11.0));
+        assert_eq!(evaluate("1 + 2 < 4").await, Value::Number(-1.0));
+        assert_eq!(evaluate("1 OR 2 AND 0").await, Value::Number(1.0));
+        assert_eq!(evaluate("1 XOR 1 OR 1").await, Value::Number(0.0));
+    }
+
+    #[tokio::test]
+    async fn new_clears_options() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "10 OPTION EXPLICIT\n20 DIM A\n30 LET A = 1",
+        )
+        .await
+        .unwrap();
+        interpreter.new_program().unwrap();
+
+        let line = Parser::with_dialect("LET B = 2", Dialect::Standard)
+            .parse()
+            .unwrap()
+            .remove(0);
+        interpreter.eval(line).await.unwrap();
+
+        assert_eq!(variable(&interpreter, "B"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn nested_include_resolves_against_parent_directory() {
+        assert_eq!(resolve_include("lib/a.bas", "b.bas"), "lib/b.bas");
+        assert_eq!(resolve_include("lib/sub/a", "b"), "lib/sub/b");
+        assert_eq!(resolve_include("a.bas", "b.bas"), "b.bas");
+    }
+
+    #[tokio::test]
+    async fn assert_reports_the_values_it_compared() {
+        let mut interpreter = Interpreter::new();
+        let lines =
+            Parser::with_dialect("10 LET A = 1\n30 ASSERT INKEY$ = \"x\"", Dialect::Standard)
+                .parse()
+                .unwrap();
+        interpreter.set_lines(lines).unwrap();
+        interpreter.reset().unwrap();
+        interpreter
+            .context
+            .events
+            .keys
+            .get_mut()
+            .extend("ab".chars());
+
+        let error = interpreter.run().await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Assertion failed at line 30: \"a\" = \"x\""
+        );
+    }
+
+    #[tokio::test]
+    async fn assert_reports_a_false_condition() {
+        let mut interpreter = Interpreter::new();
+        let error = run(&mut interpreter, "20 ASSERT 0, \"flag\"")
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "Assertion failed at line 20: flag (0)");
+    }
+
+    #[tokio::test]
+    async fn handler_can_jump_backwards_before_returning() {
+        let source = "5 LET C = 0\n6 LET N = 0\n10 ON KEY GOSUB 100\n20 KEY ON\n\
+                      30 LET C = C + 1\n40 IF C < 20 THEN GOTO 30\n50 END\n\
+                      90 LET N = N + 1\n95 RETURN\n100 GOTO 90";
+        let interpreter = run_with_keys(source, "ab").await;
+
+        assert_eq!(variable(&interpreter, "N"), Value::Number(2.0));
+        assert_eq!(variable(&interpreter, "C"), Value::Number(20.0));
+    }
+
+    #[tokio::test]
+    async fn failed_swap_changes_neither_variable() {
+        let mut interpreter = Interpreter::new();
+        let error = run(
+            &mut interpreter,
+            "10 DIM A(3)\n20 LET B = 5\n30 SWAP B, A(9)",
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, RuntimeError::SubscriptOutOfRange(_)));
+        assert_eq!(variable(&interpreter, "B"), Value::Number(5.0));
+
+        let mut interpreter = Interpreter::new();
+        let error = run(
+            &mut interpreter,
+            "10 CONST K = 1\n20 LET B = 2\n30 SWAP B, K",
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, RuntimeError::AssignmentToConstant(..)));
+        assert_eq!(variable(&interpreter, "B"), Value::Number(2.0));
+        assert_eq!(variable(&interpreter, "K"), Value::Number(1.0));
+    }
+
+    #[tokio::test]
+    async fn option_strict_rejects_a_number_in_a_string_variable() {
+        let mut interpreter = Interpreter::new();
+        let error = run(&mut interpreter, "10 OPTION STRICT\n20 LET A$ = 1")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, RuntimeError::TypeMismatch(_)));
+    }
+
+    #[tokio::test]
+    async fn included_library_is_read_again_on_each_run() {
+        let storage = std::env::temp_dir().join(format!("tinybasic-{}", std::process::id()));
+        std::fs::create_dir_all(&storage).unwrap();
+        std::env::set_var("TINYBASIC_STORAGE", &storage);
+
+        let library = storage.join("lib.bas");
+        std::fs::write(&library, "1000 LET A = 1\n1010 RETURN").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let source = "10 INCLUDE \"lib.bas\"\n20 GOSUB 1000";
+        run(&mut interpreter, source).await.unwrap();
+        assert_eq!(variable(&interpreter, "A"), Value::Number(1.0));
+
+        std::fs::write(&library, "1000 LET A = 2\n1010 RETURN").unwrap();
+        interpreter.visit_run_statement().await.unwrap();
+        assert_eq!(variable(&interpreter, "A"), Value::Number(2.0));
+        assert_eq!(interpreter.listing(), source);
+
+        std::fs::remove_dir_all(&storage).unwrap();
+    }
+
+    #[tokio::test]
+    async fn handler_suspended_by_yield_blocks_other_events() {
+        let source = "5 LET N = 0\n6 LET H = 0\n7 LET R = 0\n8 LET C = 0\n\
+                      10 ON KEY GOSUB 100\n20 SPAWN 200\n30 KEY ON\n\
+                      40 LET C = C + 1\n50 YIELD\n60 IF C < 10 THEN GOTO 40\n70 WAIT\n80 END\n\
+                      100 IF H = 1 THEN LET R = 1\n105 LET H = 1\n110 LET N = N + 1\n\
+                      115 YIELD\n118 LET H = 0\n120 RETURN\n\
+                      200 LET T = 0\n210 GOSUB 300\n220 YIELD\n230 LET T = T + 1\n\
+                      240 IF T < 10 THEN GOTO 210\n250 RETURN\n300 RETURN";
+        let interpreter = run_with_keys(source, "ab").await;
+
+        assert_eq!(variable(&interpreter, "R"), Value::Number(0.0));
+        assert_eq!(variable(&interpreter, "N"), Value::Number(2.0));
+        assert_eq!(variable(&interpreter, "T"), Value::Number(10.0));
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_outlive_the_main_program() {
+        let mut interpreter = Interpreter::new();
+        let source = "10 LET T = 0\n20 SPAWN 50\n30 GOTO 100\n\
+                      50 LET T = T + 1\n60 YIELD\n70 IF T < 5 THEN GOTO 50\n80 RETURN\n\
+                      100 LET M = 1";
+        run(&mut interpreter, source).await.unwrap();
+
+        assert_eq!(variable(&interpreter, "M"), Value::Number(1.0));
+        assert_eq!(variable(&interpreter, "T"), Value::Number(5.0));
+    }
+
+    #[tokio::test]
+    async fn assert_in_direct_mode_reports_no_line() {
+        let mut interpreter = Interpreter::new();
+        let mut lines = Parser::with_dialect("ASSERT 1 = 2", Dialect::Standard)
+            .parse()
+            .unwrap();
+        let error = interpreter.eval(lines.remove(0)).await.unwrap_err();
+
+        assert_eq!(error.to_string(), "Assertion failed: 1 = 2");
+    }
+
+    #[tokio::test]
+    async fn test_that_stops_or_ends_early_fails() {
+        let mut interpreter = Interpreter::new();
+        let lines = Parser::with_dialect(
+            "10 REM TEST_STOPS\n20 STOP\n30 RETURN\n40 REM TEST_ENDS\n50 END\n\
+             60 REM TEST_RETURNS\n70 RETURN",
+            Dialect::Standard,
+        )
+        .parse()
+        .unwrap();
+        interpreter.set_lines(lines).unwrap();
+
+        assert_eq!(
+            interpreter.visit_test_statement().await.unwrap(),
+            Value::String(String::from("1 passed, 2 failed"))
+        );
+    }
+
+    #[tokio::test]
+    async fn script_line_without_a_number_is_a_syntax_error() {
+        let mut interpreter = Interpreter::new();
+        let code = interpreter
+            .run_script(String::from("10 LET A = 1\nLET B = 2\n20 END"))
+            .await;
+
+        assert_eq!(code, 2);
+        assert!(interpreter.context.variables.is_empty());
     }
 }
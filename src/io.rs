@@ -1,4 +1,12 @@
 use std::io::{stdin, stdout, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Component, Path, PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::clock::DateTime;
 
 use wasm_bindgen::prelude::*;
 
@@ -9,6 +17,7 @@ extern "C" {
     fn terminal_write(line: &str);
     fn terminal_clear();
     fn terminal_set_prompt(prompt: &str);
+    fn terminal_poll_key() -> String;
 }
 
 #[wasm_bindgen(js_namespace = io)]
@@ -16,16 +25,70 @@ extern "C" {
     #[wasm_bindgen(catch)]
     async fn io_load_file() -> Result<JsValue, JsValue>;
     fn io_save_file(source: &str);
+    async fn io_sleep(milliseconds: f64);
+    fn io_read_file(name: &str) -> Option<String>;
+    #[wasm_bindgen(catch)]
+    fn io_write_file(name: &str, contents: &str) -> Result<(), JsValue>;
+    fn io_arguments() -> js_sys::Array;
+    fn io_environment(name: &str) -> Option<String>;
+    fn io_exit(code: i32);
+}
+
+// Terminal settings to restore when leaving raw mode
+#[cfg(not(target_arch = "wasm32"))]
+static COOKED_MODE: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_raw_mode() -> bool {
+    COOKED_MODE.lock().unwrap().is_some()
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 pub async fn read_line() -> String {
+    // INPUT inside a running program still reads whole, echoed lines
+    let raw = is_raw_mode();
+    if raw {
+        set_raw_mode(false);
+    }
+
     let mut buffer = String::new();
     stdin().read_line(&mut buffer).unwrap();
 
+    if raw {
+        set_raw_mode(true);
+    }
+
     buffer
 }
 
+// Raw mode only turns off line buffering and echo, Ctrl-C still interrupts
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_raw_mode(enabled: bool) {
+    let mut cooked = COOKED_MODE.lock().unwrap();
+    if enabled == cooked.is_some() {
+        return;
+    }
+
+    if !enabled {
+        if let Some(termios) = cooked.take() {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+        }
+        return;
+    }
+
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    // Not a terminal, keys can't be told apart from piped input
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+        return;
+    }
+    *cooked = Some(termios);
+
+    termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+    termios.c_cc[libc::VMIN] = 0;
+    termios.c_cc[libc::VTIME] = 0;
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn write(line: &str) {
     print!("{}", line);
@@ -49,6 +112,92 @@ pub fn set_prompt(prompt: &str) {
     stdout().flush().unwrap();
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+pub fn poll_key() -> Option<char> {
+    if !is_raw_mode() {
+        return None;
+    }
+
+    let lead = read_byte(0)?;
+    // A multi-byte key arrives as one UTF-8 sequence, wait briefly for the rest
+    let length = match lead {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    };
+
+    let mut bytes = vec![lead];
+    while bytes.len() < length {
+        match read_byte(10) {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+
+    Some(
+        std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|key| key.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER),
+    )
+}
+
+// Reads one byte from stdin if it arrives within the timeout
+#[cfg(not(target_arch = "wasm32"))]
+fn read_byte(timeout: i32) -> Option<u8> {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    if unsafe { libc::poll(&mut fd, 1, timeout) } <= 0 {
+        return None;
+    }
+
+    let mut byte = 0u8;
+    match unsafe {
+        libc::read(
+            libc::STDIN_FILENO,
+            &mut byte as *mut u8 as *mut libc::c_void,
+            1,
+        )
+    } {
+        1 => Some(byte),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> DateTime {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let seconds = now.as_secs() as libc::time_t;
+
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&seconds, &mut tm) };
+
+    DateTime {
+        year: tm.tm_year + 1900,
+        month: (tm.tm_mon + 1) as u32,
+        day: tm.tm_mday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+        millisecond: now.subsec_millis(),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(milliseconds: u64) {
+    tokio::time::sleep(Duration::from_millis(milliseconds)).await;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn yield_now() {
+    tokio::task::yield_now().await;
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub async fn load_file() -> Option<String> {
     None
@@ -57,6 +206,55 @@ pub async fn load_file() -> Option<String> {
 #[cfg(not(target_arch = "wasm32"))]
 pub fn save_file(source: &str) {}
 
+// Data files live in a sandbox directory, TINYBASIC_STORAGE or ./storage
+#[cfg(not(target_arch = "wasm32"))]
+fn storage_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let root = std::env::var("TINYBASIC_STORAGE").unwrap_or(String::from("storage"));
+
+    Some(Path::new(&root).join(path))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_file(name: &str) -> Option<String> {
+    std::fs::read_to_string(storage_path(name)?).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_file(name: &str, contents: &str) -> Result<(), String> {
+    let path = storage_path(name).ok_or(String::from("invalid file name"))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    std::fs::write(path, contents).map_err(|error| error.to_string())
+}
+
+// Arguments after the script path, tinybasic <script> [<argument>...]
+#[cfg(not(target_arch = "wasm32"))]
+pub fn command_arguments() -> Vec<String> {
+    std::env::args().skip(2).collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn environment(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn exit(code: i32) {
+    set_raw_mode(false);
+    stdout().flush().unwrap();
+    std::process::exit(code);
+}
+
 #[cfg(target_arch = "wasm32")]
 pub async fn read_line() -> String {
     let value = terminal_read_line().await;
@@ -88,6 +286,41 @@ pub fn set_prompt(prompt: &str) {
     terminal_set_prompt(prompt);
 }
 
+// The page queues keys by itself whenever no line is being read
+#[cfg(target_arch = "wasm32")]
+pub fn set_raw_mode(enabled: bool) {}
+
+#[cfg(target_arch = "wasm32")]
+pub fn poll_key() -> Option<char> {
+    terminal_poll_key().chars().next()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> DateTime {
+    let now = js_sys::Date::new_0();
+
+    DateTime {
+        year: now.get_full_year() as i32,
+        month: now.get_month() + 1,
+        day: now.get_date(),
+        hour: now.get_hours(),
+        minute: now.get_minutes(),
+        second: now.get_seconds(),
+        millisecond: now.get_milliseconds(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(milliseconds: u64) {
+    io_sleep(milliseconds as f64).await;
+}
+
+// Lets the page deliver key presses to a program busy polling INKEY$
+#[cfg(target_arch = "wasm32")]
+pub async fn yield_now() {
+    io_sleep(0.0).await;
+}
+
 #[cfg(target_arch = "wasm32")]
 pub async fn load_file() -> Option<String> {
     let value = io_load_file().await;
@@ -102,3 +335,31 @@ pub async fn load_file() -> Option<String> {
 pub fn save_file(source: &str) {
     io_save_file(source);
 }
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_file(name: &str) -> Option<String> {
+    io_read_file(name)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_file(name: &str, contents: &str) -> Result<(), String> {
+    io_write_file(name, contents).map_err(|error| format!("{:?}", error))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn command_arguments() -> Vec<String> {
+    io_arguments()
+        .iter()
+        .filter_map(|argument| argument.as_string())
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn environment(name: &str) -> Option<String> {
+    io_environment(name)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn exit(code: i32) {
+    io_exit(code);
+}
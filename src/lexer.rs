@@ -4,6 +4,18 @@ use crate::errors::SyntaxError;
 
 type LexerResult<T> = std::result::Result<T, SyntaxError>;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Standard,
+    PaloAlto,
+}
+
+// Palo Alto Tiny BASIC resolves "P." style abbreviations in this order
+const PALO_ALTO_KEYWORDS: &[&str] = &[
+    "LIST", "RUN", "NEW", "LET", "IF", "GOTO", "GOSUB", "RETURN", "REM", "INPUT", "PRINT", "STOP",
+    "RND", "ABS", "SIZE",
+];
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
     StringLiteral,
@@ -12,6 +24,8 @@ pub enum TokenKind {
     LeftParen,
     RightParen,
     Comma,
+    Hash,
+    Comment,
     Add,
     Subtract,
     Multiply,
@@ -60,15 +74,26 @@ impl Token {
 pub struct Lexer<'a> {
     source: &'a str,
     chars: Chars<'a>,
+    // Characters consumed so far, spans and errors count characters rather than bytes
+    position: usize,
     next_token: LexerResult<Token>,
+    // Byte offset where the peeked token starts, for slicing the source
+    token_offset: usize,
+    dialect: Dialect,
+    // Set after REM so the rest of the line is read verbatim
+    comment_pending: bool,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: &'a str) -> Self {
+    pub fn with_dialect(source: &'a str, dialect: Dialect) -> Self {
         let mut instance = Self {
             source,
             chars: source.chars(),
+            position: 0,
             next_token: Ok(Token::default()),
+            token_offset: 0,
+            dialect,
+            comment_pending: false,
         };
 
         instance.next_token = instance.consume_token();
@@ -78,6 +103,7 @@ impl<'a> Lexer<'a> {
 
     fn consume_number_literal(&mut self) -> LexerResult<Token> {
         let start = self.offset();
+        let position = self.position;
         while let Some(c) = self.peek_char() {
             match c {
                 '0'..='9' | '.' => {
@@ -92,8 +118,8 @@ impl<'a> Lexer<'a> {
         Ok(Token {
             kind: TokenKind::NumberLiteral,
             span: Span {
-                start,
-                end: self.offset(),
+                start: position,
+                end: self.position,
             },
             value,
         })
@@ -103,15 +129,20 @@ impl<'a> Lexer<'a> {
         self.next_char();
 
         let start = self.offset();
+        let position = self.position;
         while let Some(c) = self.peek_char() {
             match c {
                 '"' => {
                     let end = self.offset();
+                    let span = Span {
+                        start: position,
+                        end: self.position,
+                    };
                     self.next_char();
 
                     return Ok(Token {
                         kind: TokenKind::StringLiteral,
-                        span: Span { start, end },
+                        span,
                         value: self.get_value(start, end),
                     });
                 }
@@ -121,11 +152,12 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Err(SyntaxError::UnterminatedStringLiteral(self.offset()))
+        Err(SyntaxError::UnterminatedStringLiteral(self.position))
     }
 
     fn consume_identifier(&mut self) -> LexerResult<Token> {
         let start = self.offset();
+        let position = self.position;
         while let Some(c) = self.peek_char() {
             match c {
                 'A'..='Z' | 'a'..='z' => {
@@ -135,33 +167,77 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        let end = self.offset();
+        // String names such as DATE$ end with a dollar sign
+        if self.peek_char() == Some('$') {
+            self.next_char();
+        }
 
         // Identifiers are case-insensitive
-        let value = self.source[start..self.offset()].to_uppercase();
+        let mut value = self.source[start..self.offset()].to_uppercase();
+
+        if self.dialect == Dialect::PaloAlto && self.peek_char() == Some('.') {
+            match PALO_ALTO_KEYWORDS
+                .iter()
+                .find(|keyword| keyword.starts_with(value.as_str()))
+            {
+                Some(keyword) => {
+                    self.next_char();
+                    value = keyword.to_string();
+                }
+                None => return Err(SyntaxError::UnexpectedCharacter('.', self.position)),
+            }
+        }
+
+        self.comment_pending = value == "REM";
 
         Ok(Token {
             kind: TokenKind::Identifier,
-            span: Span { start, end },
+            span: Span {
+                start: position,
+                end: self.position,
+            },
             value: TokenValue::String(value),
         })
     }
 
+    fn consume_comment(&mut self) -> LexerResult<Token> {
+        let start = self.offset();
+        let position = self.position;
+        while let Some(c) = self.peek_char() {
+            match c {
+                '\n' => break,
+                _ => {
+                    self.next_char();
+                }
+            }
+        }
+
+        let end = self.offset();
+
+        Ok(Token {
+            kind: TokenKind::Comment,
+            span: Span {
+                start: position,
+                end: self.position,
+            },
+            value: TokenValue::String(self.source[start..end].trim().to_string()),
+        })
+    }
+
     fn consume_new_line(&mut self) -> LexerResult<Token> {
         self.next_char();
 
         Ok(Token {
             kind: TokenKind::Eol,
             span: Span {
-                start: self.offset(),
-                end: self.offset(),
+                start: self.position,
+                end: self.position,
             },
             value: TokenValue::None,
         })
     }
 
     fn consume_whitespace(&mut self) {
-        let start = self.offset();
         while let Some(c) = self.peek_char() {
             match c {
                 ' ' => {
@@ -173,11 +249,22 @@ impl<'a> Lexer<'a> {
     }
 
     fn consume_token(&mut self) -> LexerResult<Token> {
+        self.token_offset = self.offset();
+        if self.comment_pending {
+            self.comment_pending = false;
+            return self.consume_comment();
+        }
+
         while let Some(c) = self.peek_char() {
+            self.token_offset = self.offset();
             match c {
                 '\n' => return self.consume_new_line(),
+                '\'' => {
+                    self.next_char();
+                    return self.consume_comment();
+                }
                 '"' => return self.consume_string_literal(),
-                '+' | '-' | '*' | '/' | '(' | ')' | ',' | '=' | '<' | '>' => {
+                '+' | '-' | '*' | '/' | '(' | ')' | ',' | '#' | '=' | '<' | '>' => {
                     self.next_char();
 
                     let kind = match c {
@@ -188,6 +275,8 @@ impl<'a> Lexer<'a> {
                         '(' => TokenKind::LeftParen,
                         ')' => TokenKind::RightParen,
                         ',' => TokenKind::Comma,
+                        '#' if self.dialect == Dialect::PaloAlto => TokenKind::NotEqual,
+                        '#' => TokenKind::Hash,
                         '=' => TokenKind::Equal,
                         '>' => match self.peek_char() {
                             Some('=') => {
@@ -210,13 +299,26 @@ impl<'a> Lexer<'a> {
                         _ => unreachable!(),
                     };
 
-                    let start = self.offset();
-                    let end = self.offset();
-
                     return Ok(Token {
                         kind,
-                        span: Span { start, end },
-                        value: self.get_value(start, end),
+                        span: Span {
+                            start: self.position,
+                            end: self.position,
+                        },
+                        value: TokenValue::None,
+                    });
+                }
+                '@' if self.dialect == Dialect::PaloAlto => {
+                    let start = self.position;
+                    self.next_char();
+
+                    return Ok(Token {
+                        kind: TokenKind::Identifier,
+                        span: Span {
+                            start,
+                            end: self.position,
+                        },
+                        value: TokenValue::String(String::from("@")),
                     });
                 }
                 '0'..='9' => return self.consume_number_literal(),
@@ -225,7 +327,7 @@ impl<'a> Lexer<'a> {
                     self.consume_whitespace();
                 }
                 _ => {
-                    return Err(SyntaxError::UnexpectedCharacter(c, self.offset()));
+                    return Err(SyntaxError::UnexpectedCharacter(c, self.position));
                 }
             };
         }
@@ -233,8 +335,8 @@ impl<'a> Lexer<'a> {
         Ok(Token {
             kind: TokenKind::Eof,
             span: Span {
-                start: self.offset(),
-                end: self.offset(),
+                start: self.position,
+                end: self.position,
             },
             value: TokenValue::None,
         })
@@ -272,10 +374,23 @@ impl<'a> Lexer<'a> {
     }
 
     fn next_char(&mut self) -> Option<char> {
-        self.chars.next()
+        let c = self.chars.next();
+        if c.is_some() {
+            self.position += 1;
+        }
+
+        c
     }
 
     pub fn offset(&self) -> usize {
         self.source.len() - self.chars.as_str().len()
     }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn token_offset(&self) -> usize {
+        self.token_offset
+    }
 }
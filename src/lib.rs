@@ -1,8 +1,13 @@
 mod ast;
+pub mod clock;
 mod errors;
+mod files;
 mod lexer;
+mod matrix;
+pub mod memory;
 mod parser;
 mod io;
 mod program;
+mod random;
 
 pub mod interpreter;
@@ -1,10 +1,15 @@
 mod ast;
+mod clock;
 mod errors;
+mod files;
 mod interpreter;
 mod io;
 mod lexer;
+mod matrix;
+mod memory;
 mod parser;
 mod program;
+mod random;
 
 use interpreter::Interpreter;
 
@@ -12,6 +17,20 @@ use interpreter::Interpreter;
 async fn main() {
     let mut interpreter = Interpreter::new();
 
+    // tinybasic <script> [<argument>...] runs the script and exits with its status
+    if let Some(path) = std::env::args().nth(1) {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            }
+        };
+
+        let code = interpreter.run_script(source).await;
+        std::process::exit(code);
+    }
+
     let buffer: Vec<&str> = vec![
         "10 PRINT \"HELLO, WORLD!\"",
         "20 PRINT \"HELLO, WORLD!\"",
@@ -0,0 +1,136 @@
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub rows: usize,
+    pub columns: usize,
+    // Row major
+    pub values: Vec<f32>,
+}
+
+impl Matrix {
+    pub fn new(rows: usize, columns: usize, value: f32) -> Matrix {
+        Matrix {
+            rows,
+            columns,
+            values: vec![value; rows * columns],
+        }
+    }
+
+    pub fn identity(rows: usize, columns: usize) -> Option<Matrix> {
+        if rows != columns {
+            return None;
+        }
+
+        let mut matrix = Matrix::new(rows, columns, 0.0);
+        for i in 0..rows {
+            matrix.set(i, i, 1.0);
+        }
+
+        Some(matrix)
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f32 {
+        self.values[row * self.columns + column]
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, value: f32) {
+        self.values[row * self.columns + column] = value;
+    }
+
+    fn zip(&self, other: &Matrix, f: impl Fn(f32, f32) -> f32) -> Option<Matrix> {
+        if self.rows != other.rows || self.columns != other.columns {
+            return None;
+        }
+
+        Some(Matrix {
+            rows: self.rows,
+            columns: self.columns,
+            values: self
+                .values
+                .iter()
+                .zip(&other.values)
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+        })
+    }
+
+    pub fn add(&self, other: &Matrix) -> Option<Matrix> {
+        self.zip(other, |a, b| a + b)
+    }
+
+    pub fn subtract(&self, other: &Matrix) -> Option<Matrix> {
+        self.zip(other, |a, b| a - b)
+    }
+
+    pub fn multiply(&self, other: &Matrix) -> Option<Matrix> {
+        if self.columns != other.rows {
+            return None;
+        }
+
+        let mut result = Matrix::new(self.rows, other.columns, 0.0);
+        for row in 0..self.rows {
+            for column in 0..other.columns {
+                let sum = (0..self.columns)
+                    .map(|k| self.get(row, k) * other.get(k, column))
+                    .sum();
+                result.set(row, column, sum);
+            }
+        }
+
+        Some(result)
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut result = Matrix::new(self.columns, self.rows, 0.0);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                result.set(column, row, self.get(row, column));
+            }
+        }
+
+        result
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None when singular
+    pub fn inverse(&self) -> Option<Matrix> {
+        let n = self.rows;
+        let mut matrix = self.clone();
+        let mut result = Matrix::identity(n, self.columns)?;
+
+        for column in 0..n {
+            let pivot = (column..n).max_by(|a, b| {
+                matrix
+                    .get(*a, column)
+                    .abs()
+                    .total_cmp(&matrix.get(*b, column).abs())
+            })?;
+            if matrix.get(pivot, column).abs() < f32::EPSILON {
+                return None;
+            }
+
+            for k in 0..n {
+                matrix.values.swap(pivot * n + k, column * n + k);
+                result.values.swap(pivot * n + k, column * n + k);
+            }
+
+            let scale = matrix.get(column, column);
+            for k in 0..n {
+                matrix.set(column, k, matrix.get(column, k) / scale);
+                result.set(column, k, result.get(column, k) / scale);
+            }
+
+            for row in 0..n {
+                let factor = matrix.get(row, column);
+                if row == column || factor == 0.0 {
+                    continue;
+                }
+
+                for k in 0..n {
+                    matrix.set(row, k, matrix.get(row, k) - factor * matrix.get(column, k));
+                    result.set(row, k, result.get(row, k) - factor * result.get(column, k));
+                }
+            }
+        }
+
+        Some(result)
+    }
+}
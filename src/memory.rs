@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+pub const MEMORY_SIZE: usize = 64 * 1024;
+
+pub type Routine = Box<dyn Fn(&Memory, &[f32]) -> f32>;
+
+pub struct Memory {
+    bytes: Box<[u8]>,
+    routines: HashMap<u16, Routine>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory::new()
+    }
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            bytes: vec![0; MEMORY_SIZE].into_boxed_slice(),
+            routines: HashMap::new(),
+        }
+    }
+
+    pub fn peek(&self, address: u16) -> u8 {
+        self.bytes[address as usize]
+    }
+
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.bytes[address as usize] = value;
+    }
+
+    pub fn register(&mut self, address: u16, routine: Routine) {
+        self.routines.insert(address, routine);
+    }
+
+    pub fn call(&self, address: u16, arguments: &[f32]) -> Option<f32> {
+        self.routines
+            .get(&address)
+            .map(|routine| routine(self, arguments))
+    }
+}
@@ -3,20 +3,31 @@ use std::vec;
 
 use crate::ast::*;
 use crate::errors::SyntaxError;
-use crate::lexer::{Lexer, TokenKind, TokenValue};
+use crate::lexer::{Dialect, Lexer, Token, TokenKind, TokenValue};
 
 pub type ParseResult<T> = Result<T, SyntaxError>;
 
+// NOT binds looser than relations, NOT A = B is NOT (A = B)
+const NOT_PRECEDENCE: usize = 4;
+const RELATION_PRECEDENCE: usize = 5;
+
+enum Operator {
+    Arithmetic(ArithmeticOperator),
+    Relation(RelationOperator),
+}
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     source: &'a str,
+    dialect: Dialect,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(source: &'a str) -> Self {
+    pub fn with_dialect(source: &'a str, dialect: Dialect) -> Self {
         Self {
-            lexer: Lexer::new(source),
+            lexer: Lexer::with_dialect(source, dialect),
             source,
+            dialect,
         }
     }
 
@@ -66,12 +77,22 @@ impl<'a> Parser<'a> {
 
                 expression
             }
-            TokenKind::Identifier => Expression::Identifier(Identifier {
-                name: match next_token.value {
-                    TokenValue::String(s) => s.clone(),
-                    _ => Err(SyntaxError::UnexpectedToken(next_token))?,
-                },
-            }),
+            TokenKind::Identifier => {
+                let identifier = Identifier {
+                    name: match next_token.value {
+                        TokenValue::String(s) => s.clone(),
+                        _ => Err(SyntaxError::UnexpectedToken(next_token))?,
+                    },
+                };
+
+                if self.lexer.peek()?.kind != TokenKind::LeftParen {
+                    Expression::Identifier(identifier)
+                } else if self.dialect == Dialect::PaloAlto && identifier.name == "RND" {
+                    self.parse_palo_alto_rnd(identifier)?
+                } else {
+                    self.parse_call_expression(identifier)?
+                }
+            }
             TokenKind::NumberLiteral => Expression::Literal(Literal::Number {
                 value: match next_token.value {
                     TokenValue::Digit(d) => d,
@@ -97,96 +118,352 @@ impl<'a> Parser<'a> {
         }))
     }
 
+    // RND(N) is an integer between 1 and N, rewritten as INT(RND(1) * N) + 1
+    fn parse_palo_alto_rnd(&mut self, callee: Identifier) -> ParseResult<Expression> {
+        let range = match self.parse_call_expression(callee.clone())? {
+            Expression::CallExpression(call) if call.arguments.len() == 1 => {
+                call.arguments[0].clone()
+            }
+            _ => {
+                return Err(SyntaxError::UnexpectedIdentifier(
+                    callee.name,
+                    self.lexer.position(),
+                ))
+            }
+        };
+
+        let random = Expression::CallExpression(CallExpression {
+            callee,
+            arguments: vec![Expression::Literal(Literal::Number { value: 1.0 })],
+        });
+
+        Ok(Expression::BinaryExpression(BinaryExpression {
+            operator: ArithmeticOperator::Add,
+            left: Box::new(Expression::CallExpression(CallExpression {
+                callee: Identifier {
+                    name: String::from("INT"),
+                },
+                arguments: vec![Expression::BinaryExpression(BinaryExpression {
+                    operator: ArithmeticOperator::Multiply,
+                    left: Box::new(random),
+                    right: Box::new(range),
+                })],
+            })),
+            right: Box::new(Expression::Literal(Literal::Number { value: 1.0 })),
+        }))
+    }
+
+    fn parse_call_expression(&mut self, callee: Identifier) -> ParseResult<Expression> {
+        // skip (
+        self.lexer.next()?;
+
+        let mut arguments: Vec<Expression> = vec![];
+        if self.lexer.peek()?.kind != TokenKind::RightParen {
+            loop {
+                arguments.push(self.parse_expression()?);
+
+                if self.lexer.peek()?.kind != TokenKind::Comma {
+                    break;
+                }
+
+                self.lexer.next()?;
+            }
+        }
+
+        self.expect_token(&[TokenKind::RightParen], None)?;
+        self.lexer.next()?;
+
+        Ok(Expression::CallExpression(CallExpression {
+            callee,
+            arguments,
+        }))
+    }
+
     fn get_expression_precedence(&self, operator: &ArithmeticOperator) -> usize {
         match operator {
-            ArithmeticOperator::Add | ArithmeticOperator::Subtract => 1,
-            ArithmeticOperator::Multiply | ArithmeticOperator::Divide => 2,
+            ArithmeticOperator::Xor => 1,
+            ArithmeticOperator::Or => 2,
+            ArithmeticOperator::And => 3,
+            ArithmeticOperator::Add | ArithmeticOperator::Subtract => 6,
+            ArithmeticOperator::Multiply | ArithmeticOperator::Divide => 7,
         }
     }
 
+    fn peek_operator(&self) -> ParseResult<Option<Operator>> {
+        let next_token = self.lexer.peek()?;
+        let operator = match next_token.kind {
+            TokenKind::Add => Operator::Arithmetic(ArithmeticOperator::Add),
+            TokenKind::Subtract => Operator::Arithmetic(ArithmeticOperator::Subtract),
+            TokenKind::Multiply => Operator::Arithmetic(ArithmeticOperator::Multiply),
+            TokenKind::Divide => Operator::Arithmetic(ArithmeticOperator::Divide),
+            TokenKind::Equal => Operator::Relation(RelationOperator::Equal),
+            TokenKind::NotEqual => Operator::Relation(RelationOperator::NotEqual),
+            TokenKind::LessThan => Operator::Relation(RelationOperator::LessThan),
+            TokenKind::LessThanOrEqual => Operator::Relation(RelationOperator::LessThanOrEqual),
+            TokenKind::GreaterThan => Operator::Relation(RelationOperator::GreaterThan),
+            TokenKind::GreaterThanOrEqual => {
+                Operator::Relation(RelationOperator::GreaterThanOrEqual)
+            }
+            TokenKind::Identifier => match next_token.value {
+                TokenValue::String(s) => match s.as_str() {
+                    "AND" => Operator::Arithmetic(ArithmeticOperator::And),
+                    "OR" => Operator::Arithmetic(ArithmeticOperator::Or),
+                    "XOR" => Operator::Arithmetic(ArithmeticOperator::Xor),
+                    _ => return Ok(None),
+                },
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(operator))
+    }
+
     pub fn parse_expression(&mut self) -> ParseResult<Expression> {
-        let left = self.parse_unary_expression()?;
+        self.parse_binary_expression(0)
+    }
 
-        let operator = match self.lexer.peek()?.kind {
-            TokenKind::Add => Some(ArithmeticOperator::Add),
-            TokenKind::Subtract => Some(ArithmeticOperator::Subtract),
-            TokenKind::Multiply => Some(ArithmeticOperator::Multiply),
-            TokenKind::Divide => Some(ArithmeticOperator::Divide),
-            _ => None,
+    // Precedence climbing, operators of equal precedence group to the left
+    fn parse_binary_expression(&mut self, min_precedence: usize) -> ParseResult<Expression> {
+        let mut left = if self.consume_keyword("NOT")? {
+            Expression::UnaryExpression(UnaryExpression {
+                operator: Some(UnaryOperator::Not),
+                argument: Box::new(self.parse_binary_expression(NOT_PRECEDENCE + 1)?),
+            })
+        } else {
+            self.parse_unary_expression()?
         };
 
-        if operator.is_none() {
-            return Ok(left);
+        while let Some(operator) = self.peek_operator()? {
+            let precedence = match &operator {
+                Operator::Arithmetic(operator) => self.get_expression_precedence(operator),
+                Operator::Relation(_) => RELATION_PRECEDENCE,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+
+            // skip operator
+            self.lexer.next()?;
+
+            let right = Box::new(self.parse_binary_expression(precedence + 1)?);
+            left = match operator {
+                Operator::Arithmetic(operator) => Expression::BinaryExpression(BinaryExpression {
+                    operator,
+                    left: Box::new(left),
+                    right,
+                }),
+                Operator::Relation(operator) => {
+                    Expression::RelationExpression(RelationExpression {
+                        operator,
+                        left: Box::new(left),
+                        right,
+                    })
+                }
+            };
         }
 
-        // skip operator
-        self.lexer.next()?;
+        Ok(left)
+    }
 
-        let right = self.parse_expression()?;
-        let operator = operator.unwrap();
+    fn parse_channel(&mut self) -> ParseResult<Expression> {
+        if self.lexer.peek()?.kind == TokenKind::Hash {
+            self.lexer.next()?;
+        }
 
-        match &right {
-            Expression::BinaryExpression(right_expression) => {
-                let left_precedence = self.get_expression_precedence(&operator);
-                let right_precedence = self.get_expression_precedence(&right_expression.operator);
+        self.parse_expression()
+    }
 
-                if left_precedence < right_precedence {
-                    return Ok(Expression::BinaryExpression(BinaryExpression {
-                        operator,
-                        left: Box::new(right),
-                        right: Box::new(left),
-                    }));
-                }
-            }
-            _ => {}
-        };
+    fn parse_file_channel(&mut self) -> ParseResult<Expression> {
+        // skip #
+        self.lexer.next()?;
 
-        Ok(Expression::BinaryExpression(BinaryExpression {
-            operator,
-            left: Box::new(left),
-            right: Box::new(right),
-        }))
+        let channel = self.parse_expression()?;
+
+        self.expect_token(&[TokenKind::Comma], None)?;
+        self.lexer.next()?;
+
+        Ok(channel)
     }
 
     fn parse_print_statement(&mut self) -> ParseResult<Statement> {
+        let channel = match self.lexer.peek()?.kind {
+            TokenKind::Hash => Some(self.parse_file_channel()?),
+            _ => None,
+        };
+
         let mut expressions: Vec<Expression> = vec![];
         loop {
             let expression = self.parse_expression()?;
 
             expressions.push(expression);
             if self.lexer.peek()?.kind != TokenKind::Comma {
-                return Ok(Statement::PrintStatement { expressions });
+                return Ok(match channel {
+                    Some(channel) => Statement::PrintFileStatement {
+                        channel,
+                        expressions,
+                    },
+                    None => Statement::PrintStatement { expressions },
+                });
             }
 
             self.lexer.next()?;
         }
     }
 
-    fn parse_input_statement(&mut self) -> ParseResult<Statement> {
+    fn parse_variable(&mut self) -> ParseResult<Identifier> {
+        self.expect_token(&[TokenKind::Identifier], None)?;
+
+        Ok(Identifier {
+            name: match self.lexer.next()?.value {
+                TokenValue::String(s) => s.clone(),
+                _ => {
+                    let current_token = self.lexer.peek()?;
+                    return Err(SyntaxError::UnexpectedToken(current_token));
+                }
+            },
+        })
+    }
+
+    fn parse_variable_list(&mut self) -> ParseResult<Vec<Identifier>> {
         let mut variables: Vec<Identifier> = vec![];
         loop {
-            self.expect_token(&[TokenKind::Identifier], None)?;
-            variables.push(Identifier {
-                name: match self.lexer.next()?.value {
-                    TokenValue::String(s) => s.clone(),
-                    _ => {
-                        let current_token = self.lexer.peek()?;
-                        return Err(SyntaxError::UnexpectedToken(current_token));
-                    }
-                },
-            });
+            variables.push(self.parse_variable()?);
 
             if self.lexer.peek()?.kind != TokenKind::Comma {
-                return Ok(Statement::InputStatement { variables });
+                return Ok(variables);
             }
 
             self.lexer.next()?;
         }
     }
 
-    fn parse_if_statement(&mut self) -> ParseResult<Statement> {
-        let left = self.parse_expression()?;
+    fn parse_input_statement(&mut self) -> ParseResult<Statement> {
+        if self.lexer.peek()?.kind == TokenKind::Hash {
+            let channel = self.parse_file_channel()?;
+            let variables = self.parse_variable_list()?;
+
+            return Ok(Statement::InputFileStatement { channel, variables });
+        }
+
+        let variables = self.parse_variable_list()?;
+
+        Ok(Statement::InputStatement { variables })
+    }
+
+    fn parse_line_input_statement(&mut self) -> ParseResult<Statement> {
+        self.expect_keyword("INPUT")?;
+        self.expect_token(&[TokenKind::Hash], None)?;
+
+        let channel = self.parse_file_channel()?;
+        let variable = self.parse_variable()?;
+
+        Ok(Statement::LineInputFileStatement { channel, variable })
+    }
+
+    fn parse_open_statement(&mut self) -> ParseResult<Statement> {
+        let path = self.parse_expression()?;
+
+        // Without a FOR clause the file is opened for random access
+        let mode = if !self.consume_keyword("FOR")? {
+            FileMode::Random
+        } else if self.consume_keyword("INPUT")? {
+            FileMode::Input
+        } else if self.consume_keyword("OUTPUT")? {
+            FileMode::Output
+        } else if self.consume_keyword("APPEND")? {
+            FileMode::Append
+        } else {
+            self.expect_keyword("RANDOM")?;
+            FileMode::Random
+        };
+
+        self.expect_keyword("AS")?;
+
+        let channel = self.parse_channel()?;
+
+        let length = if self.consume_keyword("LEN")? {
+            self.expect_token(&[TokenKind::Equal], None)?;
+            self.lexer.next()?;
+
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
+        Ok(Statement::OpenStatement {
+            path,
+            mode,
+            channel,
+            length,
+        })
+    }
+
+    fn parse_field_statement(&mut self) -> ParseResult<Statement> {
+        self.expect_token(&[TokenKind::Hash], None)?;
 
+        let channel = self.parse_file_channel()?;
+
+        let mut fields: Vec<FieldDeclaration> = vec![];
+        loop {
+            let width = self.parse_expression()?;
+            self.expect_keyword("AS")?;
+            let variable = self.parse_variable()?;
+
+            fields.push(FieldDeclaration { width, variable });
+
+            if self.lexer.peek()?.kind != TokenKind::Comma {
+                return Ok(Statement::FieldStatement { channel, fields });
+            }
+
+            self.lexer.next()?;
+        }
+    }
+
+    fn parse_record_access(&mut self) -> ParseResult<(Expression, Option<Expression>)> {
+        let channel = self.parse_channel()?;
+
+        if self.lexer.peek()?.kind != TokenKind::Comma {
+            return Ok((channel, None));
+        }
+
+        self.lexer.next()?;
+
+        Ok((channel, Some(self.parse_expression()?)))
+    }
+
+    fn parse_close_statement(&mut self) -> ParseResult<Statement> {
+        let mut channels: Vec<Expression> = vec![];
+
+        if self.at_statement_end()? {
+            return Ok(Statement::CloseStatement { channels });
+        }
+
+        loop {
+            channels.push(self.parse_channel()?);
+
+            if self.lexer.peek()?.kind != TokenKind::Comma {
+                return Ok(Statement::CloseStatement { channels });
+            }
+
+            self.lexer.next()?;
+        }
+    }
+
+    fn consume_keyword(&mut self, keyword: &str) -> ParseResult<bool> {
+        let next_token = self.lexer.peek()?;
+
+        if next_token.kind == TokenKind::Identifier
+            && next_token.value == TokenValue::String(keyword.to_string())
+        {
+            self.lexer.next()?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn parse_relation_operator(&mut self) -> ParseResult<RelationOperator> {
         self.expect_token(
             &[
                 TokenKind::Equal,
@@ -210,24 +487,23 @@ impl<'a> Parser<'a> {
             _ => Err(SyntaxError::UnexpectedToken(next_token))?,
         };
 
-        let right = self.parse_expression()?;
+        Ok(relation_operator)
+    }
 
-        self.expect_token(
-            &[TokenKind::Identifier],
-            Some(TokenValue::String(String::from("THEN"))),
-        )?;
+    fn parse_if_statement(&mut self) -> ParseResult<Statement> {
+        let condition = self.parse_expression()?;
 
-        // skip THEN
-        self.lexer.next()?;
+        // Palo Alto Tiny BASIC has no THEN
+        if self.dialect == Dialect::PaloAlto {
+            self.consume_keyword("THEN")?;
+        } else {
+            self.expect_keyword("THEN")?;
+        }
 
         let then = self.parse_statement()?;
 
         return Ok(Statement::IfStatement {
-            condition: IfCondition {
-                operator: relation_operator,
-                left,
-                right,
-            },
+            condition,
             then: Box::new(then),
         });
     }
@@ -236,20 +512,192 @@ impl<'a> Parser<'a> {
         self.expect_token(&[TokenKind::Identifier], None)?;
 
         let next_token = self.lexer.next()?;
+
+        self.parse_assignment(next_token)
+    }
+
+    fn parse_subscripts(&mut self) -> ParseResult<Vec<Expression>> {
+        self.expect_token(&[TokenKind::LeftParen], None)?;
+        self.lexer.next()?;
+
+        let mut indices: Vec<Expression> = vec![];
+        loop {
+            indices.push(self.parse_expression()?);
+
+            if self.lexer.peek()?.kind != TokenKind::Comma {
+                break;
+            }
+
+            self.lexer.next()?;
+        }
+
+        self.expect_token(&[TokenKind::RightParen], None)?;
+        self.lexer.next()?;
+
+        Ok(indices)
+    }
+
+    fn parse_dim_statement(&mut self) -> ParseResult<Statement> {
+        let mut declarations: Vec<ArrayDeclaration> = vec![];
+        loop {
+            let name = self.parse_variable()?.name;
+            let dimensions = if self.lexer.peek()?.kind == TokenKind::LeftParen {
+                self.parse_subscripts()?
+            } else {
+                vec![]
+            };
+            declarations.push(ArrayDeclaration { name, dimensions });
+
+            if self.lexer.peek()?.kind != TokenKind::Comma {
+                return Ok(Statement::DimStatement { declarations });
+            }
+
+            self.lexer.next()?;
+        }
+    }
+
+    fn parse_data_statement(&mut self) -> ParseResult<Statement> {
+        let mut values: Vec<Expression> = vec![];
+        loop {
+            values.push(self.parse_expression()?);
+
+            if self.lexer.peek()?.kind != TokenKind::Comma {
+                return Ok(Statement::DataStatement { values });
+            }
+
+            self.lexer.next()?;
+        }
+    }
+
+    fn parse_restore_statement(&mut self) -> ParseResult<Statement> {
+        if self.at_statement_end()? {
+            return Ok(Statement::RestoreStatement { location: None });
+        }
+
+        Ok(Statement::RestoreStatement {
+            location: Some(self.parse_expression()?),
+        })
+    }
+
+    fn parse_matrix_size(&mut self) -> ParseResult<Vec<Expression>> {
+        if self.lexer.peek()?.kind != TokenKind::LeftParen {
+            return Ok(vec![]);
+        }
+
+        self.parse_subscripts()
+    }
+
+    fn parse_matrix_argument(&mut self) -> ParseResult<Identifier> {
+        self.expect_token(&[TokenKind::LeftParen], None)?;
+        self.lexer.next()?;
+
+        let array = self.parse_variable()?;
+
+        self.expect_token(&[TokenKind::RightParen], None)?;
+        self.lexer.next()?;
+
+        Ok(array)
+    }
+
+    fn parse_mat_statement(&mut self) -> ParseResult<Statement> {
+        if self.consume_keyword("READ")? {
+            return Ok(Statement::MatReadStatement {
+                arrays: self.parse_variable_list()?,
+            });
+        }
+
+        if self.consume_keyword("PRINT")? {
+            return Ok(Statement::MatPrintStatement {
+                arrays: self.parse_variable_list()?,
+            });
+        }
+
+        let target = self.parse_variable()?;
+
+        self.expect_token(&[TokenKind::Equal], None)?;
+        self.lexer.next()?;
+
+        let value = if self.consume_keyword("ZER")? {
+            MatrixExpression::Zero(self.parse_matrix_size()?)
+        } else if self.consume_keyword("CON")? {
+            MatrixExpression::Constant(self.parse_matrix_size()?)
+        } else if self.consume_keyword("IDN")? {
+            MatrixExpression::Identity(self.parse_matrix_size()?)
+        } else if self.consume_keyword("TRN")? {
+            MatrixExpression::Transpose(self.parse_matrix_argument()?)
+        } else if self.consume_keyword("INV")? {
+            MatrixExpression::Inverse(self.parse_matrix_argument()?)
+        } else {
+            let left = self.parse_variable()?;
+
+            match self.lexer.peek()?.kind {
+                TokenKind::Add => {
+                    self.lexer.next()?;
+                    MatrixExpression::Add(left, self.parse_variable()?)
+                }
+                TokenKind::Subtract => {
+                    self.lexer.next()?;
+                    MatrixExpression::Subtract(left, self.parse_variable()?)
+                }
+                TokenKind::Multiply => {
+                    self.lexer.next()?;
+                    MatrixExpression::Multiply(left, self.parse_variable()?)
+                }
+                _ => MatrixExpression::Array(left),
+            }
+        };
+
+        Ok(Statement::MatStatement { target, value })
+    }
+
+    fn parse_assert_statement(&mut self) -> ParseResult<Statement> {
+        let condition = self.parse_expression()?;
+
+        if self.lexer.peek()?.kind != TokenKind::Comma {
+            return Ok(Statement::AssertStatement {
+                condition,
+                message: None,
+            });
+        }
+        self.lexer.next()?;
+
+        Ok(Statement::AssertStatement {
+            condition,
+            message: Some(self.parse_expression()?),
+        })
+    }
+
+    fn parse_swap_statement(&mut self) -> ParseResult<Statement> {
+        let left = self.parse_expression()?;
+
+        self.expect_token(&[TokenKind::Comma], None)?;
+        self.lexer.next()?;
+
+        let right = self.parse_expression()?;
+
+        Ok(Statement::SwapStatement { left, right })
+    }
+
+    fn parse_assignment(&mut self, next_token: Token) -> ParseResult<Statement> {
         let name = next_token.value;
 
         match name.clone() {
-            TokenValue::String(name) => {
-                if name.len() > 1 {
-                    return Err(SyntaxError::InvalidVariableName(
-                        name,
-                        next_token.span.start,
-                    ));
-                }
+            // A single letter, string variables add a trailing $
+            TokenValue::String(name) if name.trim_end_matches('$').len() > 1 => {
+                return Err(SyntaxError::InvalidVariableName(
+                    name,
+                    next_token.span.start,
+                ));
             }
             _ => {}
         };
 
+        let indices = if self.lexer.peek()?.kind == TokenKind::LeftParen {
+            self.parse_subscripts()?
+        } else {
+            vec![]
+        };
+
         self.expect_token(&[TokenKind::Equal], None)?;
         self.lexer.next()?;
 
@@ -257,11 +705,12 @@ impl<'a> Parser<'a> {
 
         return Ok(Statement::VarStatement {
             declaration: VarDeclaration {
+                indices,
                 name: match name {
                     TokenValue::String(s) => s.to_uppercase(),
                     _ => Err(SyntaxError::UnexpectedIdentifier(
                         format!("{:?}", value),
-                        self.lexer.offset(),
+                        self.lexer.position(),
                     ))?,
                 },
                 value,
@@ -281,24 +730,265 @@ impl<'a> Parser<'a> {
         return Ok(Statement::GoSubStatement { location });
     }
 
-    fn parse_rem_statement(&mut self) -> ParseResult<Statement> {
+    fn parse_select_statement(&mut self) -> ParseResult<Statement> {
+        self.expect_token(
+            &[TokenKind::Identifier],
+            Some(TokenValue::String(String::from("CASE"))),
+        )?;
+
+        // skip CASE
+        self.lexer.next()?;
+
+        let discriminant = self.parse_expression()?;
+
+        Ok(Statement::SelectStatement { discriminant })
+    }
+
+    fn parse_case_test(&mut self) -> ParseResult<CaseTest> {
+        if self.consume_keyword("IS")? {
+            let operator = self.parse_relation_operator()?;
+            let value = self.parse_expression()?;
+
+            return Ok(CaseTest::Is { operator, value });
+        }
+
+        let from = self.parse_expression()?;
+
+        if self.consume_keyword("TO")? {
+            let to = self.parse_expression()?;
+
+            return Ok(CaseTest::Range { from, to });
+        }
+
+        Ok(CaseTest::Value(from))
+    }
+
+    fn parse_case_statement(&mut self) -> ParseResult<Statement> {
+        if self.consume_keyword("ELSE")? {
+            return Ok(Statement::CaseElseStatement);
+        }
+
+        let mut tests: Vec<CaseTest> = vec![];
         loop {
-            let next_token = self.lexer.peek()?;
-            if next_token.kind == TokenKind::Eol || next_token.kind == TokenKind::Eof {
-                break;
+            tests.push(self.parse_case_test()?);
+
+            if self.lexer.peek()?.kind != TokenKind::Comma {
+                return Ok(Statement::CaseStatement { tests });
             }
+
             self.lexer.next()?;
         }
+    }
+
+    fn parse_end_statement(&mut self) -> ParseResult<Statement> {
+        if self.consume_keyword("SELECT")? {
+            return Ok(Statement::EndSelectStatement);
+        }
+
+        Ok(Statement::EndStatement {
+            code: self.parse_optional_expression()?,
+        })
+    }
+
+    fn parse_optional_expression(&mut self) -> ParseResult<Option<Expression>> {
+        if self.at_statement_end()? {
+            return Ok(None);
+        }
+
+        Ok(Some(self.parse_expression()?))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> ParseResult<()> {
+        self.expect_token(
+            &[TokenKind::Identifier],
+            Some(TokenValue::String(keyword.to_string())),
+        )?;
+        self.lexer.next()?;
+
+        Ok(())
+    }
+
+    fn parse_on_statement(&mut self) -> ParseResult<Statement> {
+        if self.consume_keyword("TIMER")? {
+            self.expect_token(&[TokenKind::LeftParen], None)?;
+            self.lexer.next()?;
+
+            let interval = self.parse_expression()?;
+
+            self.expect_token(&[TokenKind::RightParen], None)?;
+            self.lexer.next()?;
+
+            self.expect_keyword("GOSUB")?;
+            let location = self.parse_expression()?;
+
+            return Ok(Statement::OnTimerStatement { interval, location });
+        }
 
-        Ok(Statement::RemStatement)
+        if self.consume_keyword("KEY")? {
+            self.expect_keyword("GOSUB")?;
+            let location = self.parse_expression()?;
+
+            return Ok(Statement::OnKeyStatement { location });
+        }
+
+        self.expect_keyword("ERROR")?;
+        self.expect_keyword("GOTO")?;
+
+        let location = self.parse_expression()?;
+
+        Ok(Statement::OnErrorStatement { location })
+    }
+
+    fn parse_switch(&mut self) -> ParseResult<bool> {
+        if self.consume_keyword("ON")? {
+            return Ok(true);
+        }
+
+        self.expect_keyword("OFF")?;
+
+        Ok(false)
+    }
+
+    fn parse_resume_statement(&mut self) -> ParseResult<Statement> {
+        if self.at_statement_end()? {
+            return Ok(Statement::ResumeStatement {
+                target: ResumeTarget::Retry,
+            });
+        }
+
+        if self.consume_keyword("NEXT")? {
+            return Ok(Statement::ResumeStatement {
+                target: ResumeTarget::Next,
+            });
+        }
+
+        let location = self.parse_expression()?;
+
+        Ok(Statement::ResumeStatement {
+            target: ResumeTarget::Line(location),
+        })
+    }
+
+    fn parse_randomize_statement(&mut self) -> ParseResult<Statement> {
+        if self.at_statement_end()? {
+            return Ok(Statement::RandomizeStatement { seed: None });
+        }
+
+        let seed = self.parse_expression()?;
+
+        Ok(Statement::RandomizeStatement { seed: Some(seed) })
+    }
+
+    fn parse_poke_statement(&mut self) -> ParseResult<Statement> {
+        let address = self.parse_expression()?;
+
+        self.expect_token(&[TokenKind::Comma], None)?;
+        self.lexer.next()?;
+
+        let value = self.parse_expression()?;
+
+        Ok(Statement::PokeStatement { address, value })
+    }
+
+    fn parse_option_statement(&mut self) -> ParseResult<Statement> {
+        let directive = if self.consume_keyword("EXPLICIT")? {
+            OptionDirective::Explicit
+        } else if self.consume_keyword("STRICT")? {
+            OptionDirective::Strict
+        } else if self.consume_keyword("BASE")? {
+            let next_token = self.lexer.peek()?;
+            match next_token.value {
+                TokenValue::Digit(base) if base == 0.0 || base == 1.0 => {
+                    self.lexer.next()?;
+                    OptionDirective::Base(base as usize)
+                }
+                _ => return Err(SyntaxError::UnexpectedToken(next_token)),
+            }
+        } else {
+            self.expect_keyword("COMPARE")?;
+
+            if self.consume_keyword("TEXT")? {
+                OptionDirective::Compare(Collation::CaseInsensitive)
+            } else {
+                self.expect_keyword("BINARY")?;
+                OptionDirective::Compare(Collation::Ordinal)
+            }
+        };
+
+        Ok(Statement::OptionStatement { directive })
+    }
+
+    fn parse_const_statement(&mut self) -> ParseResult<Statement> {
+        let name = self.parse_variable()?.name;
+
+        self.expect_token(&[TokenKind::Equal], None)?;
+        self.lexer.next()?;
+
+        let value = self.parse_expression()?;
+
+        Ok(Statement::ConstStatement { name, value })
+    }
+
+    fn parse_dialect_statement(&mut self) -> ParseResult<Statement> {
+        let dialect = if self.consume_keyword("PALOALTO")? {
+            Dialect::PaloAlto
+        } else {
+            self.expect_keyword("STANDARD")?;
+            Dialect::Standard
+        };
+
+        Ok(Statement::DialectStatement { dialect })
+    }
+
+    fn at_statement_end(&self) -> ParseResult<bool> {
+        Ok(matches!(
+            self.lexer.peek()?.kind,
+            TokenKind::Eol | TokenKind::Eof | TokenKind::Comment
+        ))
+    }
+
+    fn parse_rem_statement(&mut self) -> ParseResult<Statement> {
+        Ok(Statement::RemStatement {
+            text: self.parse_comment()?,
+        })
+    }
+
+    fn parse_comment(&mut self) -> ParseResult<String> {
+        let next_token = self.lexer.peek()?;
+        if next_token.kind != TokenKind::Comment {
+            return Ok(String::new());
+        }
+        self.lexer.next()?;
+
+        Ok(match next_token.value {
+            TokenValue::String(s) => s,
+            _ => String::new(),
+        })
     }
 
     fn parse_statement(&mut self) -> ParseResult<Statement> {
+        // ' is shorthand for REM
+        if self.lexer.peek()?.kind == TokenKind::Comment {
+            return self.parse_rem_statement();
+        }
+
         let next_token = self.lexer.next()?;
         let statement = match next_token.value {
             TokenValue::String(s) => match s.as_str() {
                 "PRINT" => self.parse_print_statement(),
                 "INPUT" => self.parse_input_statement(),
+                "LINE" => self.parse_line_input_statement(),
+                "OPEN" => self.parse_open_statement(),
+                "CLOSE" => self.parse_close_statement(),
+                "FIELD" => self.parse_field_statement(),
+                "GET" => {
+                    let (channel, record) = self.parse_record_access()?;
+                    Ok(Statement::GetStatement { channel, record })
+                }
+                "PUT" => {
+                    let (channel, record) = self.parse_record_access()?;
+                    Ok(Statement::PutStatement { channel, record })
+                }
                 "IF" => self.parse_if_statement(),
                 "LET" => self.parse_var_statement(),
                 "GOTO" => self.parse_goto_statement(),
@@ -307,12 +997,90 @@ impl<'a> Parser<'a> {
                 "RUN" => Ok(Statement::RunStatement),
                 "LIST" => Ok(Statement::ListStatement),
                 "CLS" => Ok(Statement::ClsStatement),
-                "RETURN" => Ok(Statement::ReturnStatement),
-                "END" => Ok(Statement::EndStatement),
+                "RETURN" => Ok(Statement::ReturnStatement {
+                    location: self.parse_optional_expression()?,
+                }),
+                "END" => self.parse_end_statement(),
+                "SYSTEM" => Ok(Statement::SystemStatement {
+                    code: self.parse_optional_expression()?,
+                }),
+                "SELECT" => self.parse_select_statement(),
+                "CASE" => self.parse_case_statement(),
+                "ON" => self.parse_on_statement(),
+                "RESUME" => self.parse_resume_statement(),
+                "TIMER" => Ok(Statement::TimerStatement {
+                    enabled: self.parse_switch()?,
+                }),
+                "KEY" => Ok(Statement::KeyStatement {
+                    enabled: self.parse_switch()?,
+                }),
+                "TRON" => Ok(Statement::TronStatement {
+                    variables: self.consume_keyword("VARS")?,
+                }),
+                "TROFF" => Ok(Statement::TroffStatement),
+                "RANDOMIZE" => self.parse_randomize_statement(),
+                "SLEEP" => Ok(Statement::SleepStatement {
+                    duration: self.parse_expression()?,
+                }),
+                "PAUSE" => Ok(Statement::PauseStatement {
+                    duration: self.parse_expression()?,
+                }),
+                "STOP" if self.dialect == Dialect::PaloAlto => {
+                    Ok(Statement::EndStatement { code: None })
+                }
+                "STOP" => Ok(Statement::StopStatement),
+                "DIALECT" => self.parse_dialect_statement(),
+                "OPTION" => self.parse_option_statement(),
+                "CONT" => Ok(Statement::ContStatement),
                 "HELP" => Ok(Statement::HelpStatement),
                 "LOAD" => Ok(Statement::LoadStatement),
+                "POKE" => self.parse_poke_statement(),
+                "CHAIN" => Ok(Statement::ChainStatement {
+                    path: self.parse_expression()?,
+                }),
+                "MERGE" => Ok(Statement::MergeStatement {
+                    path: self.parse_expression()?,
+                }),
+                "INCLUDE" => Ok(Statement::IncludeStatement {
+                    path: self.parse_expression()?,
+                }),
+                "COMMON" => Ok(Statement::CommonStatement {
+                    variables: self.parse_variable_list()?,
+                }),
                 "SAVE" => Ok(Statement::SaveStatement),
+                "DIM" => self.parse_dim_statement(),
+                "ERASE" => Ok(Statement::EraseStatement {
+                    arrays: self.parse_variable_list()?,
+                }),
+                "SWAP" => self.parse_swap_statement(),
+                "CLEAR" => Ok(Statement::ClearStatement),
+                "VARS" => Ok(Statement::VarsStatement),
+                "CONST" => self.parse_const_statement(),
+                "DATA" => self.parse_data_statement(),
+                "READ" => Ok(Statement::ReadStatement {
+                    variables: self.parse_variable_list()?,
+                }),
+                "RESTORE" => self.parse_restore_statement(),
+                "MAT" => self.parse_mat_statement(),
+                "SPAWN" => Ok(Statement::SpawnStatement {
+                    location: self.parse_expression()?,
+                }),
+                "YIELD" => Ok(Statement::YieldStatement),
+                "WAIT" => Ok(Statement::WaitStatement),
+                "ASSERT" => self.parse_assert_statement(),
+                "TEST" => Ok(Statement::TestStatement),
                 "REM" => self.parse_rem_statement(),
+                // Implicit LET, a variable or array element followed by =
+                _ if matches!(
+                    self.lexer.peek()?.kind,
+                    TokenKind::Equal | TokenKind::LeftParen
+                ) =>
+                {
+                    self.parse_assignment(Token {
+                        value: TokenValue::String(s),
+                        ..next_token
+                    })
+                }
                 _ => Err(SyntaxError::UnexpectedIdentifier(s, next_token.span.start)),
             },
             _ => Err(SyntaxError::UnexpectedToken(next_token)),
@@ -322,6 +1090,15 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse(&mut self) -> ParseResult<Vec<Line>> {
+        self.parse_lines(false)
+    }
+
+    // A stored program, unlike direct mode input, needs a number on every line
+    pub fn parse_program(&mut self) -> ParseResult<Vec<Line>> {
+        self.parse_lines(true)
+    }
+
+    fn parse_lines(&mut self, numbered: bool) -> ParseResult<Vec<Line>> {
         let mut lines: Vec<Line> = vec![];
 
         while self.lexer.peek()?.kind != TokenKind::Eof {
@@ -330,40 +1107,45 @@ impl<'a> Parser<'a> {
                     TokenKind::Eol,
                     TokenKind::Identifier,
                     TokenKind::NumberLiteral,
+                    TokenKind::Comment,
                 ],
                 None,
             )?;
 
             let next_token = self.lexer.peek()?;
+            let start = self.lexer.token_offset();
 
             if next_token.kind == TokenKind::Eol {
                 self.lexer.next()?;
                 continue;
             }
 
-            let line = match next_token.kind {
-                TokenKind::Identifier => Ok(Line {
-                    number: None,
-                    statement: self.parse_statement()?,
-                    source: self.source[next_token.span.start..self.lexer.offset()].to_string(),
-                }),
+            let number = match next_token.kind {
                 TokenKind::NumberLiteral => {
-                    let line_number = match next_token.value {
-                        TokenValue::Digit(number) => Some(number),
-                        _ => None,
-                    };
                     self.lexer.next()?;
-
-                    Ok(Line {
-                        number: line_number.map(|n| n as usize),
-                        statement: self.parse_statement()?,
-                        source: self.source[next_token.span.start..self.lexer.offset()].to_string(),
-                    })
+                    match next_token.value {
+                        TokenValue::Digit(number) => Some(number as usize),
+                        _ => None,
+                    }
                 }
-                _ => Err(SyntaxError::UnexpectedToken(next_token)),
+                _ if numbered => return Err(SyntaxError::MissingLineNumber(next_token.span.start)),
+                _ => None,
+            };
+
+            let statement = self.parse_statement()?;
+            let comment = match self.lexer.peek()?.kind {
+                TokenKind::Comment => Some(self.parse_comment()?),
+                _ => None,
+            };
+
+            let line = Line {
+                number,
+                statement,
+                comment,
+                source: self.source[start..self.lexer.offset()].to_string(),
             };
 
-            lines.push(line?);
+            lines.push(line);
         }
 
         Ok(lines)
@@ -3,13 +3,14 @@ use crate::ast::Line;
 pub const MAX_LINES: usize = 8 * 1024;
 
 pub struct Program {
-    pub lines: [Option<Line>; MAX_LINES],
+    // Boxed, the array is too large to live on the stack
+    pub lines: Box<[Option<Line>]>,
 }
 
 impl Program {
     pub fn new() -> Program {
         Program {
-            lines: [const { None }; MAX_LINES],
+            lines: vec![None; MAX_LINES].into_boxed_slice(),
         }
     }
 
@@ -23,7 +24,7 @@ impl Program {
     }
 
     pub fn clear(&mut self) {
-        self.lines = [const { None }; MAX_LINES];
+        self.lines.fill(None);
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = Line> + 'a {
@@ -33,9 +34,13 @@ impl Program {
             .map(|line| line.clone())
     }
 
-    pub fn print(&self) -> String {
+    pub fn size(&self) -> usize {
+        self.iter().map(|line| line.source.trim().len() + 1).sum()
+    }
+
+    pub fn print(&self, visible: impl Fn(&Line) -> bool) -> String {
         let mut output: Vec<String> = vec![];
-        for line in self.iter() {
+        for line in self.iter().filter(visible) {
             output.push(format!("{}", line.source.trim()));
         }
 
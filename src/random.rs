@@ -0,0 +1,81 @@
+use std::cell::Cell;
+
+const DEFAULT_SEED: u32 = 0x2545_f491;
+
+// Xorshift generator, so a seed yields the same sequence on every target
+pub struct Random {
+    state: Cell<u32>,
+    last: Cell<f32>,
+}
+
+impl Random {
+    pub fn new() -> Random {
+        Random {
+            state: Cell::new(DEFAULT_SEED),
+            last: Cell::new(0.0),
+        }
+    }
+
+    pub fn seed(&self, seed: f32) {
+        // Scramble the bits so that close seeds start far apart
+        let mut state = seed.to_bits() ^ DEFAULT_SEED;
+        state = (state ^ (state >> 16)).wrapping_mul(0x045d_9f3b);
+        state = (state ^ (state >> 16)).wrapping_mul(0x045d_9f3b);
+        state ^= state >> 16;
+
+        self.state
+            .set(if state == 0 { DEFAULT_SEED } else { state });
+    }
+
+    pub fn next(&self) -> f32 {
+        let mut state = self.state.get();
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        self.state.set(state);
+
+        // Keep 24 bits, which is all the precision an f32 has
+        let value = (state >> 8) as f32 / (1 << 24) as f32;
+        self.last.set(value);
+
+        value
+    }
+
+    // RND(x): a negative x reseeds, zero repeats the last number
+    pub fn rnd(&self, x: f32) -> f32 {
+        if x < 0.0 {
+            self.seed(x);
+        } else if x == 0.0 {
+            return self.last.get();
+        }
+
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_produces_fixed_sequence() {
+        let random = Random::new();
+        random.seed(42.0);
+        let values: Vec<f32> = (0..5).map(|_| random.next()).collect();
+
+        assert_eq!(
+            values,
+            [0.47216564, 0.99275726, 0.80087245, 0.22306019, 0.70178926]
+        );
+    }
+
+    #[test]
+    fn rnd_negative_reseeds_and_zero_repeats() {
+        let random = Random::new();
+        let first = random.rnd(-3.0);
+        assert_eq!(random.rnd(0.0), first);
+
+        random.rnd(1.0);
+        assert_eq!(random.rnd(-3.0), first);
+    }
+}
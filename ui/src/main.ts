@@ -4,6 +4,8 @@ const terminal = document.querySelector<HTMLDivElement>('#terminal')!;
 const input = document.querySelector<HTMLInputElement>('#input')!;
 const output = document.querySelector<HTMLDivElement>('#output')!;
 let interpreter: Interpreter;
+let reading = false;
+const keys: string[] = [];
 
 const write = (text: string) => {
     output.innerText += text;
@@ -16,6 +18,7 @@ const clear = () => {
 
 const readLine = () =>
     new Promise<string>((resolve) => {
+        reading = true;
         const controller = new AbortController();
         input.addEventListener(
             'keydown',
@@ -25,6 +28,7 @@ const readLine = () =>
                     input.innerText = '';
 
                     controller.abort();
+                    reading = false;
                     resolve(value);
                 }
             },
@@ -36,6 +40,16 @@ const setPrompt = (prompt: string) => {
     input.dataset.prompt = prompt;
 };
 
+// INKEY$ sees the control characters a terminal would send
+const specialKeys: Record<string, string> = {
+    Enter: '\r',
+    Backspace: '\b',
+    Tab: '\t',
+    Escape: '\x1b'
+};
+
+const pollKey = () => keys.shift() ?? '';
+
 const loadFile = async () => {
     const [fileHandle] = await window.showOpenFilePicker({
         types: [
@@ -71,16 +85,47 @@ const saveFile = async (source: string) => {
     await writable.close();
 };
 
+const readFile = (name: string) => localStorage.getItem(`tinybasic:${name}`) ?? undefined;
+
+const writeFile = (name: string, contents: string) => {
+    localStorage.setItem(`tinybasic:${name}`, contents);
+};
+
+// ARGV$ and ENVIRON$ come from the page URL, ?arg=a&arg=b&env=NAME=value
+const parameters = new URLSearchParams(window.location.search);
+
+const argumentList = () => parameters.getAll('arg');
+
+const environment = (name: string) =>
+    parameters
+        .getAll('env')
+        .find((entry) => entry.startsWith(`${name}=`))
+        ?.slice(name.length + 1);
+
+const exit = (code: number) => {
+    write(`Exited with status ${code}\n`);
+};
+
+const sleep = (milliseconds: number) =>
+    new Promise<void>((resolve) => setTimeout(resolve, milliseconds));
+
 (window as any).terminal = {
     terminal_write: write,
     terminal_read_line: readLine,
     terminal_clear: clear,
-    terminal_set_prompt: setPrompt
+    terminal_set_prompt: setPrompt,
+    terminal_poll_key: pollKey
 };
 
 (window as any).io = {
     io_load_file: loadFile,
-    io_save_file: saveFile
+    io_save_file: saveFile,
+    io_sleep: sleep,
+    io_read_file: readFile,
+    io_write_file: writeFile,
+    io_arguments: argumentList,
+    io_environment: environment,
+    io_exit: exit
 };
 
 clear();
@@ -90,6 +135,15 @@ init().then(() => {
     });
 
     input.addEventListener('keydown', (e) => {
+        // Keys typed while a program runs are queued for the interpreter
+        if (!reading && e.key.length === 1) {
+            e.preventDefault();
+            keys.push(e.key);
+        } else if (!reading && e.key in specialKeys) {
+            e.preventDefault();
+            keys.push(specialKeys[e.key]);
+        }
+
         switch (e.key) {
             case 'ArrowUp':
             case 'ArrowLeft':